@@ -1,9 +0,0 @@
-# a = [1,0,0,0]
-# a = [4891460686036598785, 2896914383306846353, 13281191951274694749, 3486998266802970665]
-a = [
-            1936300593443367654,
-            14433331277405977980,
-            16585481364922334147,
-            2807817091631785586,
-        ]
-print(a[0] + (a[1] << 64) + (a[2] << 128) + (a[3] << 192))
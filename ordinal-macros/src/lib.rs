@@ -56,6 +56,42 @@ pub fn to_sign_and_limbs(input: TokenStream) -> TokenStream {
     quote::quote!(#tuple).into()
 }
 
+/// BN254 scalar field modulus
+const FR_MODULUS: &str = "21888242871839275222246405745257275088548364400416034343698204186575808495617";
+
+fn to_u64_limbs(num: &BigInt) -> Vec<u64> {
+    let mut limbs = num.to_u64_digits().1;
+    limbs.resize(4, 0);
+    limbs
+}
+
+/// `(hi, lo)` words of a non-negative decimal below 2^256, for the const
+/// `U256::from_words`.
+#[proc_macro]
+pub fn to_u256_words(input: TokenStream) -> TokenStream {
+    let num = parse_string(input).expect("expected decimal string");
+    let num = BigInt::from_str(&num).expect("could not parse to bigint");
+    assert!(num.sign() != Sign::Minus && num.bits() <= 256, "expected an unsigned 256 bit integer");
+    let limbs = to_u64_limbs(&num);
+    let hi = (limbs[3] as u128) << 64 | limbs[2] as u128;
+    let lo = (limbs[1] as u128) << 64 | limbs[0] as u128;
+    let tuple: Expr = syn::parse_str(&format!("({}u128, {}u128)", hi, lo)).unwrap();
+    quote::quote!(#tuple).into()
+}
+
+/// Little endian limbs of a decimal reduced modulo the BN254 scalar field,
+/// in Montgomery form (times 2^256), for the const `Fr::new`.
+#[proc_macro]
+pub fn to_montgomery_limbs(input: TokenStream) -> TokenStream {
+    let num = parse_string(input).expect("expected decimal string");
+    let modulus = BigInt::from_str(FR_MODULUS).unwrap();
+    let num = BigInt::from_str(&num).expect("could not parse to bigint");
+    let mont = ((num % &modulus + &modulus) << 256u32) % &modulus;
+    let limbs: Vec<String> = to_u64_limbs(&mont).iter().map(|l| format!("{}u64", l)).collect();
+    let array: Expr = syn::parse_str(&format!("[{}]", limbs.join(", "))).unwrap();
+    quote::quote!(#array).into()
+}
+
 #[test]
 fn test_str_to_limbs() {
     let (is_positive, limbs) = str_to_limbs("-5");
@@ -0,0 +1,268 @@
+//! https://github.com/iden3/circomlib/blob/master/src/babyjub.js
+//!
+//! Baby Jubjub, the twisted Edwards curve `a x^2 + y^2 = 1 + d x^2 y^2` over
+//! the BN254 scalar field.
+use ark_bn254::{Fr, FrParameters};
+use ark_ff::{BigInteger256, Field, FpParameters, One, SquareRootField, Zero};
+use ethnum::U256;
+use subtle::{Choice, ConditionallySelectable};
+
+use crate::bignum;
+use crate::convert::{fr_from_u256, fr_from_u256_mod, fr_to_u256, limbs_to_u256};
+use crate::utils::{ct_bit, ct_pow};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Point {
+    pub x: Fr,
+    pub y: Fr,
+}
+
+fn a() -> Fr {
+    Fr::from(168700u64)
+}
+
+fn d() -> Fr {
+    Fr::from(168696u64)
+}
+
+fn modulus() -> U256 {
+    limbs_to_u256(&FrParameters::MODULUS.0)
+}
+
+/// `x` is negative if it is above `(p - 1) / 2`.
+fn is_negative(x: &Fr) -> bool {
+    fr_to_u256(x) > modulus() >> 1
+}
+
+/// Order of the prime subgroup the generators live in.
+pub fn sub_order() -> U256 {
+    *bignum!("2736030358979909402780800718157159386076813972158567259200215660948447373041")
+}
+
+impl Point {
+    pub fn identity() -> Self {
+        Self { x: Fr::zero(), y: Fr::one() }
+    }
+
+    /// Generator of the prime subgroup, `Base8` in circomlib.
+    pub fn base8() -> Self {
+        Self {
+            x: fr_from_u256_mod(&*bignum!("5299619240641551281634865583518297030282874472190772894086521144482721001553")),
+            y: fr_from_u256_mod(&*bignum!("16950150798460657717958625567821834550301663161624707787222815936182638968203")),
+        }
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        let (x1, y1, x2, y2) = (self.x, self.y, other.x, other.y);
+        let dxy = d() * x1 * x2 * y1 * y2;
+        let x = (x1 * y2 + y1 * x2) * (Fr::one() + dxy).inverse().expect("complete addition");
+        let y = (y1 * y2 - a() * x1 * x2) * (Fr::one() - dxy).inverse().expect("complete addition");
+        Self { x, y }
+    }
+
+    /// Double-and-add, most significant bit first.
+    pub fn mul_scalar(&self, e: &U256) -> Self {
+        let mut acc = Self::identity();
+        for i in (0..(256 - e.leading_zeros())).rev() {
+            acc = acc.add(&acc);
+            if (e >> i) & U256::ONE == U256::ONE {
+                acc = acc.add(self);
+            }
+        }
+        acc
+    }
+
+    /// `mul_scalar` for secret scalars: a doubling and an addition for every
+    /// one of the 256 bits, projective coordinates so there is no inversion
+    /// per step, and `subtle` selection instead of branches. That is the
+    /// whole guarantee: no branches or table lookups on the scalar bits. The
+    /// curve arithmetic is arkworks `Fr`, whose reduction may branch on the
+    /// values, so timing is not constant at the instruction level.
+    pub fn mul_scalar_ct(&self, e: &U256) -> Self {
+        self.mul_projective_ct(e).into()
+    }
+
+    /// `mul_scalar_ct` without the final inversion, for callers that keep
+    /// adding to the result.
+    pub(crate) fn mul_projective_ct(&self, e: &U256) -> Projective {
+        let base = Projective::from(*self);
+        let mut acc = Projective::identity();
+        for i in (0..256).rev() {
+            acc = acc.add(&acc);
+            let sum = acc.add(&base);
+            acc = Projective::select(ct_bit(e, i), &sum, &acc);
+        }
+        acc
+    }
+
+    pub fn in_curve(&self) -> bool {
+        let (x2, y2) = (self.x.square(), self.y.square());
+        a() * x2 + y2 == Fr::one() + d() * x2 * y2
+    }
+
+    pub fn in_subgroup(&self) -> bool {
+        self.in_curve() && self.mul_scalar(&sub_order()) == Self::identity()
+    }
+
+    /// `y` little endian with the sign of `x` in the top bit.
+    pub fn pack(&self) -> [u8; 32] {
+        let mut bytes = fr_to_u256(&self.y).to_le_bytes();
+        if is_negative(&self.x) {
+            bytes[31] |= 0x80;
+        }
+        bytes
+    }
+
+    pub fn unpack(bytes: &[u8; 32]) -> Option<Self> {
+        let mut bytes = *bytes;
+        let sign = bytes[31] & 0x80 != 0;
+        bytes[31] &= 0x7f;
+        let y = U256::from_le_bytes(bytes);
+        if y >= modulus() {
+            return None;
+        }
+
+        let y = fr_from_u256_mod(&y);
+        let y2 = y.square();
+        let x2 = (Fr::one() - y2) * (a() - d() * y2).inverse()?;
+        let mut x = x2.sqrt()?;
+        // take the root without sign, then apply the packed one
+        if is_negative(&x) {
+            x = -x;
+        }
+        if sign {
+            x = -x;
+        }
+        Some(Self { x, y })
+    }
+}
+
+/// `(X : Y : Z)` with `x = X / Z` and `y = Y / Z`
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Projective {
+    x: Fr,
+    y: Fr,
+    z: Fr,
+}
+
+impl Projective {
+    pub(crate) fn identity() -> Self {
+        Self { x: Fr::zero(), y: Fr::one(), z: Fr::one() }
+    }
+
+    /// add-2008-bbjlp, complete on Baby Jubjub
+    pub(crate) fn add(&self, other: &Self) -> Self {
+        let a_ = self.z * other.z;
+        let b_ = a_.square();
+        let c_ = self.x * other.x;
+        let d_ = self.y * other.y;
+        let e_ = d() * c_ * d_;
+        let f_ = b_ - e_;
+        let g_ = b_ + e_;
+        let x = a_ * f_ * ((self.x + self.y) * (other.x + other.y) - c_ - d_);
+        let y = a_ * g_ * (d_ - a() * c_);
+        Self { x, y, z: f_ * g_ }
+    }
+
+    fn select(choice: Choice, a: &Self, b: &Self) -> Self {
+        Self {
+            x: ct_select_fr(choice, &a.x, &b.x),
+            y: ct_select_fr(choice, &a.y, &b.y),
+            z: ct_select_fr(choice, &a.z, &b.z),
+        }
+    }
+}
+
+impl From<Point> for Projective {
+    fn from(p: Point) -> Self {
+        Self { x: p.x, y: p.y, z: Fr::one() }
+    }
+}
+
+/// Fermat's `z^(p - 2)` with `ct_pow`, a fixed 256 bit exponentiation, in
+/// place of arkworks' `inverse`, whose running time depends on `z`. `z` is
+/// never zero, the addition is complete.
+impl From<Projective> for Point {
+    fn from(p: Projective) -> Self {
+        let z_inv = ct_pow(&fr_to_u256(&p.z), &(modulus() - 2), &modulus());
+        let z_inv = fr_from_u256(&z_inv).expect("ct_pow reduces below the modulus");
+        Self { x: p.x * z_inv, y: p.y * z_inv }
+    }
+}
+
+/// `a` if `choice` is set else `b`, selecting the Montgomery limbs directly.
+fn ct_select_fr(choice: Choice, a: &Fr, b: &Fr) -> Fr {
+    let mut limbs = [0u64; 4];
+    for (i, limb) in limbs.iter_mut().enumerate() {
+        *limb = u64::conditional_select(&(b.0).0[i], &(a.0).0[i], choice);
+    }
+    Fr::new(BigInteger256::new(limbs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base8() {
+        let b = Point::base8();
+        assert!(b.in_curve());
+        assert!(b.in_subgroup());
+        assert_eq!(b.add(&Point::identity()), b);
+        assert_eq!(b.mul_scalar(&U256::new(3)), b.add(&b).add(&b));
+        assert_eq!(Point::unpack(&b.pack()), Some(b));
+
+        let neg = Point { x: -b.x, y: b.y };
+        assert_ne!(neg.pack(), b.pack());
+        assert_eq!(Point::unpack(&neg.pack()), Some(neg));
+        assert_eq!(b.add(&neg), Point::identity());
+    }
+
+    /// circomlib's babyjub tests
+    #[test]
+    fn test_circomlib_vectors() {
+        let point = |x: &str, y: &str| Point {
+            x: fr_from_u256_mod(&crate::convert::parse_decimal(x).unwrap()),
+            y: fr_from_u256_mod(&crate::convert::parse_decimal(y).unwrap()),
+        };
+        let p = point(
+            "17777552123799933955779906779655732241715742912184938656739573121738514868268",
+            "2626589144620713026669568689430873010625803728049924121243784502389097019475",
+        );
+        assert!(p.in_curve());
+        assert_eq!(
+            p.add(&p),
+            point(
+                "6890855772600357754907169075114257697580319025794532037257385534741338397365",
+                "4338620300185947561074059802482547481416142213883829469920100239455078257889",
+            ),
+        );
+        assert_eq!(
+            p.mul_scalar(&U256::new(3)),
+            point(
+                "19372461775513343691590086534037741906533799473648040012278229434133483800898",
+                "9458658722007214007257525444427903161243386465067105737478306991484593958249",
+            ),
+        );
+        let e = *bignum!("14035240266687799601661095864649209771790948434046947201833777492504781204499");
+        let expected = point(
+            "17070357974431721403481313912716834497662307308519659060910483826664480189605",
+            "4014745322800118607127020275658861516666525056516280575712425373174125159339",
+        );
+        assert_eq!(p.mul_scalar(&e), expected);
+        assert_eq!(p.mul_scalar_ct(&e), expected);
+    }
+
+    #[test]
+    fn test_mul_scalar_ct() {
+        let b = Point::base8();
+        let mut e = U256::new(0x9e3779b97f4a7c15);
+        for _ in 0..4 {
+            e = e.wrapping_mul(e) ^ U256::new(0x243f6a8885a308d3);
+            let e = e % sub_order();
+            assert_eq!(b.mul_scalar_ct(&e), b.mul_scalar(&e));
+        }
+        assert_eq!(b.mul_scalar_ct(&U256::ZERO), Point::identity());
+        assert_eq!(b.mul_scalar_ct(&sub_order()), Point::identity());
+    }
+}
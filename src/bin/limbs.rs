@@ -0,0 +1,46 @@
+//! Replaces `to_4_u64.py` and `from_4_u64.py`: reads one number per line from
+//! stdin. A decimal prints its little endian `u64` limbs as a Rust array
+//! literal, a limbs literal like `[1u64, 0, 0, 0]` prints the decimal.
+use std::io::{self, BufRead};
+use std::process;
+
+use ordinal_core::convert::{limbs_literal, limbs_to_u256, parse_decimal, to_decimal};
+
+fn parse_limbs(s: &str) -> Option<[u64; 4]> {
+    let inner = s.strip_prefix('[')?.strip_suffix(']')?;
+    let parsed: Vec<u64> = inner
+        .split(',')
+        .map(|limb| limb.trim().trim_end_matches("u64").parse().ok())
+        .collect::<Option<_>>()?;
+    let mut limbs = [0u64; 4];
+    if parsed.len() != limbs.len() {
+        return None;
+    }
+    limbs.copy_from_slice(&parsed);
+    Some(limbs)
+}
+
+fn convert(line: &str) -> Option<String> {
+    if line.starts_with('[') {
+        Some(to_decimal(&limbs_to_u256(&parse_limbs(line)?)))
+    } else {
+        Some(limbs_literal(&parse_decimal(line)?))
+    }
+}
+
+fn main() {
+    for line in io::stdin().lock().lines() {
+        let line = line.expect("cannot read stdin");
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match convert(line) {
+            Some(out) => println!("{}", out),
+            None => {
+                eprintln!("not a decimal below 2^256 or a 4 limb literal: {}", line);
+                process::exit(1);
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! BLAKE-256 (the 14 round SHA-3 finalist), as used by circomlib to derive
+//! the Pedersen generators.
+
+const IV: [u32; 8] = [
+    0x6A09E667, 0xBB67AE85, 0x3C6EF372, 0xA54FF53A, 0x510E527F, 0x9B05688C, 0x1F83D9AB, 0x5BE0CD19,
+];
+
+const U: [u32; 16] = [
+    0x243F6A88, 0x85A308D3, 0x13198A2E, 0x03707344, 0xA4093822, 0x299F31D0, 0x082EFA98, 0xEC4E6C89,
+    0x452821E6, 0x38D01377, 0xBE5466CF, 0x34E90C6C, 0xC0AC29B7, 0xC97C50DD, 0x3F84D5B5, 0xB5470917,
+];
+
+const SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+const ROUNDS: usize = 14;
+
+pub fn blake256(bytes: &[u8]) -> [u8; 32] {
+    let bit_len = (bytes.len() as u64) * 8;
+
+    // 0x80, zeros, 0x01 (merged into 0x81 if there is room for one byte only),
+    // then the bit length, big endian
+    let mut padded = bytes.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    *padded.last_mut().unwrap() |= 1;
+    padded.extend_from_slice(&bit_len.to_be_bytes());
+
+    let mut h = IV;
+    for (i, block) in padded.chunks(64).enumerate() {
+        // bits of the message up to this block, zero if it holds padding only
+        let hashed = (i as u64) * 512;
+        let counter = if hashed >= bit_len { 0 } else { bit_len.min(hashed + 512) };
+        compress(&mut h, block, counter);
+    }
+
+    let mut out = [0u8; 32];
+    for (chunk, word) in out.chunks_mut(4).zip(&h) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    out
+}
+
+fn compress(h: &mut [u32; 8], block: &[u8], counter: u64) {
+    let mut m = [0u32; 16];
+    for (word, chunk) in m.iter_mut().zip(block.chunks(4)) {
+        *word = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+
+    let (t0, t1) = (counter as u32, (counter >> 32) as u32);
+    let mut v = [0u32; 16];
+    v[..8].copy_from_slice(h);
+    v[8..12].copy_from_slice(&U[..4]);
+    v[12] = t0 ^ U[4];
+    v[13] = t0 ^ U[5];
+    v[14] = t1 ^ U[6];
+    v[15] = t1 ^ U[7];
+
+    for r in 0..ROUNDS {
+        let s = &SIGMA[r % 10];
+        let mut g = |a: usize, b: usize, c: usize, d: usize, i: usize| {
+            let (x, y) = (s[2 * i], s[2 * i + 1]);
+            v[a] = v[a].wrapping_add(v[b]).wrapping_add(m[x] ^ U[y]);
+            v[d] = (v[d] ^ v[a]).rotate_right(16);
+            v[c] = v[c].wrapping_add(v[d]);
+            v[b] = (v[b] ^ v[c]).rotate_right(12);
+            v[a] = v[a].wrapping_add(v[b]).wrapping_add(m[y] ^ U[x]);
+            v[d] = (v[d] ^ v[a]).rotate_right(8);
+            v[c] = v[c].wrapping_add(v[d]);
+            v[b] = (v[b] ^ v[c]).rotate_right(7);
+        };
+        g(0, 4, 8, 12, 0);
+        g(1, 5, 9, 13, 1);
+        g(2, 6, 10, 14, 2);
+        g(3, 7, 11, 15, 3);
+        g(0, 5, 10, 15, 4);
+        g(1, 6, 11, 12, 5);
+        g(2, 7, 8, 13, 6);
+        g(3, 4, 9, 14, 7);
+    }
+
+    for i in 0..8 {
+        h[i] ^= v[i] ^ v[i + 8];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_blake256() {
+        assert_eq!(hex(&blake256(b"")), "716f6e863f744b9ac22c97ec7b76ea5f5908bc5b2f67c61510bfc4751384ea7a");
+        assert_eq!(hex(&blake256(&[0])), "0ce8d4ef4dd7cd8d62dfded9d4edb0a774ae6a41929a74da23109e8f11139c87");
+        assert_eq!(hex(&blake256(&[0; 72])), "d419bad32d504fb7d44d460c42c5593fe544fa4c135dec31e21bd9abdcc22d41");
+    }
+}
@@ -0,0 +1,218 @@
+//! Conversions between `U256`, BN254 `Fr`/`Fq`, 64-bit limbs, bytes, hex and
+//! decimal strings.
+//!
+//! Checked conversions return `None` instead of reducing: a field element
+//! must be below its modulus, byte slices at most 32 bytes long; the `_mod`
+//! variants reduce. Limbs are little endian like `BigInteger256`. The
+//! `limbs` binary converts between decimals and limb literals on stdin.
+use ark_bn254::{Fq, Fr, FrParameters};
+use ark_ff::{BigInteger256, FpParameters, PrimeField};
+
+use crate::U256;
+
+pub fn limbs_to_u256(limbs: &[u64; 4]) -> U256 {
+    let lo = limbs[0] as u128 | (limbs[1] as u128) << 64;
+    let hi = limbs[2] as u128 | (limbs[3] as u128) << 64;
+    U256::from_words(hi, lo)
+}
+
+pub fn u256_to_limbs(a: &U256) -> [u64; 4] {
+    let (hi, lo) = a.into_words();
+    [lo as u64, (lo >> 64) as u64, hi as u64, (hi >> 64) as u64]
+}
+
+/// Limbs as a Rust array literal, e.g. for `BigInteger256::new`.
+pub fn limbs_literal(a: &U256) -> String {
+    let limbs: Vec<String> = u256_to_limbs(a).iter().map(|l| format!("{}u64", l)).collect();
+    format!("[{}]", limbs.join(", "))
+}
+
+/// Up to 32 bytes, little endian.
+pub fn u256_from_le_slice(bytes: &[u8]) -> Option<U256> {
+    if bytes.len() > 32 {
+        return None;
+    }
+    let mut le = [0u8; 32];
+    le[..bytes.len()].copy_from_slice(bytes);
+    Some(U256::from_le_bytes(le))
+}
+
+/// Up to 32 bytes, big endian.
+pub fn u256_from_be_slice(bytes: &[u8]) -> Option<U256> {
+    if bytes.len() > 32 {
+        return None;
+    }
+    let mut be = [0u8; 32];
+    be[32 - bytes.len()..].copy_from_slice(bytes);
+    Some(U256::from_be_bytes(be))
+}
+
+/// Hex digits with or without a `0x` prefix, at most 64 of them.
+pub fn parse_hex(s: &str) -> Option<U256> {
+    let digits = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    U256::from_str_radix(digits, 16).ok()
+}
+
+/// `0x` followed by 64 lowercase hex digits.
+pub fn to_hex(a: &U256) -> String {
+    format!("0x{:064x}", a)
+}
+
+pub fn parse_decimal(s: &str) -> Option<U256> {
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    U256::from_str_radix(s, 10).ok()
+}
+
+pub fn to_decimal(a: &U256) -> String {
+    a.to_string()
+}
+
+pub fn fr_from_u256(a: &U256) -> Option<Fr> {
+    Fr::from_repr(BigInteger256::new(u256_to_limbs(a)))
+}
+
+/// Reduces `a` modulo the scalar field instead of rejecting it.
+pub fn fr_from_u256_mod(a: &U256) -> Fr {
+    let reduced = a % limbs_to_u256(&FrParameters::MODULUS.0);
+    fr_from_u256(&reduced).expect("reduced below the modulus")
+}
+
+pub fn fr_to_u256(a: &Fr) -> U256 {
+    limbs_to_u256(&a.into_repr().0)
+}
+
+pub fn fq_from_u256(a: &U256) -> Option<Fq> {
+    Fq::from_repr(BigInteger256::new(u256_to_limbs(a)))
+}
+
+pub fn fq_to_u256(a: &Fq) -> U256 {
+    limbs_to_u256(&a.into_repr().0)
+}
+
+pub fn fr_from_le_bytes(bytes: &[u8]) -> Option<Fr> {
+    fr_from_u256(&u256_from_le_slice(bytes)?)
+}
+
+pub fn fr_from_be_bytes(bytes: &[u8]) -> Option<Fr> {
+    fr_from_u256(&u256_from_be_slice(bytes)?)
+}
+
+pub fn fr_to_le_bytes(a: &Fr) -> [u8; 32] {
+    fr_to_u256(a).to_le_bytes()
+}
+
+pub fn fr_to_be_bytes(a: &Fr) -> [u8; 32] {
+    fr_to_u256(a).to_be_bytes()
+}
+
+pub fn fq_from_le_bytes(bytes: &[u8]) -> Option<Fq> {
+    fq_from_u256(&u256_from_le_slice(bytes)?)
+}
+
+pub fn fq_from_be_bytes(bytes: &[u8]) -> Option<Fq> {
+    fq_from_u256(&u256_from_be_slice(bytes)?)
+}
+
+pub fn fq_to_le_bytes(a: &Fq) -> [u8; 32] {
+    fq_to_u256(a).to_le_bytes()
+}
+
+pub fn fq_to_be_bytes(a: &Fq) -> [u8; 32] {
+    fq_to_u256(a).to_be_bytes()
+}
+
+/// The internal Montgomery form limbs of `a`, as stored by arkworks.
+pub fn fr_to_montgomery_limbs(a: &Fr) -> [u64; 4] {
+    (a.0).0
+}
+
+/// `None` unless the Montgomery form limbs are below the modulus.
+pub fn fr_from_montgomery_limbs(limbs: &[u64; 4]) -> Option<Fr> {
+    let fr = Fr::new(BigInteger256::new(*limbs));
+    fr_from_u256(&limbs_to_u256(limbs)).map(|_| fr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ff::field_new;
+
+    const P: &str = "21888242871839275222246405745257275088548364400416034343698204186575808495617";
+
+    #[test]
+    fn test_limbs() {
+        // what to_4_u64.py printed for the scalar field modulus
+        let p = parse_decimal(P).unwrap();
+        assert_eq!(
+            limbs_literal(&p),
+            "[4891460686036598785u64, 2896914383306846353u64, 13281191951274694749u64, 3486998266802970665u64]",
+        );
+        assert_eq!(
+            u256_to_limbs(&p),
+            [4891460686036598785, 2896914383306846353, 13281191951274694749, 3486998266802970665],
+        );
+        assert_eq!(to_decimal(&limbs_to_u256(&u256_to_limbs(&p))), P);
+    }
+
+    #[test]
+    fn test_strings() {
+        let x = U256::new(0xdead_beef);
+        assert_eq!(parse_hex("0xdeadbeef"), Some(x));
+        assert_eq!(parse_hex("DEADBEEF"), Some(x));
+        assert_eq!(to_hex(&x), format!("0x{}deadbeef", "0".repeat(56)));
+        assert_eq!(parse_hex(&to_hex(&U256::MAX)), Some(U256::MAX));
+        assert_eq!(parse_hex(""), None);
+        assert_eq!(parse_hex("0x"), None);
+        assert_eq!(parse_hex("+1"), None);
+        assert_eq!(parse_hex(&"f".repeat(65)), None);
+
+        assert_eq!(parse_decimal("3735928559"), Some(x));
+        assert_eq!(parse_decimal(&to_decimal(&U256::MAX)), Some(U256::MAX));
+        assert_eq!(parse_decimal("-1"), None);
+        assert_eq!(parse_decimal("1e3"), None);
+        assert_eq!(parse_decimal(&format!("{}0", U256::MAX)), None);
+    }
+
+    #[test]
+    fn test_bytes() {
+        let x = U256::new(0x0102);
+        assert_eq!(u256_from_le_slice(&[2, 1]), Some(x));
+        assert_eq!(u256_from_be_slice(&[1, 2]), Some(x));
+        assert_eq!(u256_from_le_slice(&x.to_le_bytes()), Some(x));
+        assert_eq!(u256_from_be_slice(&x.to_be_bytes()), Some(x));
+        assert_eq!(u256_from_le_slice(&[0; 33]), None);
+        assert_eq!(u256_from_be_slice(&[]), Some(U256::ZERO));
+    }
+
+    #[test]
+    fn test_fields() {
+        let p = parse_decimal(P).unwrap();
+        let a = field_new!(Fr, "18141211044530898481780712096785380507009040886197825359491225784587697908689");
+        let a_u256 = parse_decimal("18141211044530898481780712096785380507009040886197825359491225784587697908689").unwrap();
+        assert_eq!(fr_to_u256(&a), a_u256);
+        assert_eq!(fr_from_u256(&a_u256), Some(a));
+        assert_eq!(fr_from_u256(&p), None);
+        assert_eq!(fr_from_u256(&(p - 1)), Some(-Fr::from(1u64)));
+        assert_eq!(fr_from_u256_mod(&(p + a_u256)), a);
+        assert_eq!(fr_from_u256_mod(&p), Fr::from(0u64));
+        assert_eq!(fr_from_u256_mod(&U256::MAX), Fr::from_be_bytes_mod_order(&U256::MAX.to_be_bytes()));
+
+        assert_eq!(fr_from_le_bytes(&fr_to_le_bytes(&a)), Some(a));
+        assert_eq!(fr_from_be_bytes(&fr_to_be_bytes(&a)), Some(a));
+        assert_eq!(fr_from_be_bytes(&p.to_be_bytes()), None);
+        assert_eq!(fr_from_montgomery_limbs(&fr_to_montgomery_limbs(&a)), Some(a));
+        assert_eq!(fr_from_montgomery_limbs(&u256_to_limbs(&p)), None);
+
+        // above the scalar field modulus but still inside the base field
+        let q = fq_from_u256(&p).unwrap();
+        assert_eq!(fq_to_u256(&q), p);
+        assert_eq!(fq_from_le_bytes(&fq_to_le_bytes(&q)), Some(q));
+        assert_eq!(fq_from_be_bytes(&fq_to_be_bytes(&q)), Some(q));
+        assert_eq!(fq_from_u256(&U256::MAX), None);
+    }
+}
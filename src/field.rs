@@ -0,0 +1,187 @@
+//! Elements of a prime field over `U256`, so that the modulus is carried by
+//! the type instead of being threaded through `addmod`/`mulmod` calls.
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Add, Mul, Neg, Sub};
+
+use ark_bn254::Fr;
+use ethnum::U256;
+
+use crate::const_bignum;
+use crate::convert::{fr_from_u256_mod, fr_to_u256};
+use crate::utils::{addmod, mod_inverse, mod_pow, mulmod, normalize};
+
+pub trait FieldParams {
+    const MODULUS: U256;
+}
+
+/// BN254 scalar field, the circuit field of MiMC and the Merkle tree
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Bn254Fr;
+
+impl FieldParams for Bn254Fr {
+    const MODULUS: U256 =
+        const_bignum!("21888242871839275222246405745257275088548364400416034343698204186575808495617");
+}
+
+/// BN254 base field, the coordinates of G1
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Bn254Fq;
+
+impl FieldParams for Bn254Fq {
+    const MODULUS: U256 =
+        const_bignum!("21888242871839275222246405745257275088696311157297823662689037894645226208583");
+}
+
+/// Canonical representative in `[0, P::MODULUS)`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FieldElement<P: FieldParams> {
+    value: U256,
+    _params: PhantomData<P>,
+}
+
+impl<P: FieldParams> FieldElement<P> {
+    /// `None` unless `value` is below the modulus.
+    pub fn new(value: U256) -> Option<Self> {
+        if value < P::MODULUS {
+            Some(Self { value, _params: PhantomData })
+        } else {
+            None
+        }
+    }
+
+    pub fn from_u256_mod(value: &U256) -> Self {
+        Self { value: normalize(value, &P::MODULUS), _params: PhantomData }
+    }
+
+    pub fn zero() -> Self {
+        Self { value: U256::ZERO, _params: PhantomData }
+    }
+
+    pub fn one() -> Self {
+        Self { value: U256::ONE, _params: PhantomData }
+    }
+
+    pub fn value(&self) -> U256 {
+        self.value
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.value == U256::ZERO
+    }
+
+    pub fn pow(&self, exp: &U256) -> Self {
+        Self { value: mod_pow(&self.value, exp, &P::MODULUS), _params: PhantomData }
+    }
+
+    /// `None` for zero.
+    pub fn inverse(&self) -> Option<Self> {
+        let value = mod_inverse(&self.value, &P::MODULUS)?;
+        Some(Self { value, _params: PhantomData })
+    }
+}
+
+impl<P: FieldParams> fmt::Debug for FieldElement<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FieldElement({})", self.value)
+    }
+}
+
+impl<P: FieldParams> Add for FieldElement<P> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self { value: addmod(&self.value, &rhs.value, &P::MODULUS), _params: PhantomData }
+    }
+}
+
+impl<P: FieldParams> Sub for FieldElement<P> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        self + -rhs
+    }
+}
+
+impl<P: FieldParams> Mul for FieldElement<P> {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self { value: mulmod(&self.value, &rhs.value, &P::MODULUS), _params: PhantomData }
+    }
+}
+
+impl<P: FieldParams> Neg for FieldElement<P> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        if self.is_zero() {
+            self
+        } else {
+            Self { value: P::MODULUS - self.value, _params: PhantomData }
+        }
+    }
+}
+
+impl From<Fr> for FieldElement<Bn254Fr> {
+    fn from(fr: Fr) -> Self {
+        Self { value: fr_to_u256(&fr), _params: PhantomData }
+    }
+}
+
+impl From<FieldElement<Bn254Fr>> for Fr {
+    fn from(fe: FieldElement<Bn254Fr>) -> Self {
+        fr_from_u256_mod(&fe.value)
+    }
+}
+
+impl<P: FieldParams> From<FieldElement<P>> for U256 {
+    fn from(fe: FieldElement<P>) -> Self {
+        fe.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bn254::{Fq, FqParameters, FrParameters};
+    use ark_ff::{Field, FpParameters, PrimeField};
+
+    type F = FieldElement<Bn254Fr>;
+
+    #[test]
+    fn test_modulus() {
+        let to_u256 = |limbs: [u64; 4]| crate::convert::limbs_to_u256(&limbs);
+        assert_eq!(Bn254Fr::MODULUS, to_u256(FrParameters::MODULUS.0));
+        assert_eq!(Bn254Fq::MODULUS, to_u256(FqParameters::MODULUS.0));
+    }
+
+    #[test]
+    fn test_ops_match_fr() {
+        let a = F::from_u256_mod(&(Bn254Fr::MODULUS + 5));
+        let b = F::new(Bn254Fr::MODULUS - 3).unwrap();
+        assert!(F::new(Bn254Fr::MODULUS).is_none());
+        assert_eq!(a.value(), U256::new(5));
+
+        let (fa, fb) = (Fr::from(a), Fr::from(b));
+        assert_eq!(Fr::from(a + b), fa + fb);
+        assert_eq!(Fr::from(a - b), fa - fb);
+        assert_eq!(Fr::from(b - a), fb - fa);
+        assert_eq!(Fr::from(a * b), fa * fb);
+        assert_eq!(Fr::from(-b), -fb);
+        assert_eq!(-F::zero(), F::zero());
+        assert_eq!(Fr::from(b.pow(&U256::new(5))), fb.pow([5]));
+        assert_eq!(Fr::from(b.inverse().unwrap()), fb.inverse().unwrap());
+        assert_eq!(b * b.inverse().unwrap(), F::one());
+        assert!(F::zero().inverse().is_none());
+        assert_eq!(F::from(fb), b);
+    }
+
+    #[test]
+    fn test_fq() {
+        let x = FieldElement::<Bn254Fq>::from_u256_mod(&U256::new(7));
+        let inv = x.inverse().unwrap();
+        let expected = Fq::from(7u64).inverse().unwrap().into_repr().0;
+        assert_eq!(U256::from(inv), crate::convert::limbs_to_u256(&expected));
+    }
+}
@@ -0,0 +1,89 @@
+use ark_bn254::Fr;
+use ark_ff::Zero;
+
+use crate::mimc7::Mimc7;
+use crate::mimcsponge::MimcSponge;
+use crate::poseidon::Poseidon;
+
+/// Compression function of a binary Merkle tree.
+pub trait TreeHasher {
+    fn hash_left_right(&self, left: Fr, right: Fr) -> Fr;
+
+    /// `hash_left_right` over many independent pairs.
+    fn hash_pairs(&self, pairs: &[(Fr, Fr)]) -> Vec<Fr> {
+        pairs.iter().map(|(left, right)| self.hash_left_right(*left, *right)).collect()
+    }
+}
+
+/// circomlib `MiMCSponge(2, 220, 1)`, as used by tornado.cash
+impl TreeHasher for MimcSponge {
+    fn hash_left_right(&self, left: Fr, right: Fr) -> Fr {
+        // `multi_hash_fr(&[left, right], 0, 1)` without the output vector
+        let (r, c) = self.mimcsponge_fr(left, Fr::zero());
+        self.mimcsponge_fr(r + right, c).0
+    }
+
+    fn hash_pairs(&self, pairs: &[(Fr, Fr)]) -> Vec<Fr> {
+        self.hash_pairs_fr(pairs)
+    }
+}
+
+/// circomlib `MultiMiMC7(2, 91)` with a zero key
+impl TreeHasher for Mimc7 {
+    fn hash_left_right(&self, left: Fr, right: Fr) -> Fr {
+        self.multi_hash_fr(&[left, right], Fr::zero())
+    }
+}
+
+/// circomlib `Poseidon(2)`, the permutation must have width 3
+impl TreeHasher for Poseidon {
+    fn hash_left_right(&self, left: Fr, right: Fr) -> Fr {
+        self.hash_fr(&[left, right])
+    }
+}
+
+/// Multi-input compression function of an n-ary Merkle tree.
+pub trait NodeHasher {
+    /// Permutations one node of `arity` children costs, `None` if the hasher
+    /// cannot compress that many.
+    fn permutations(&self, arity: usize) -> Option<usize>;
+
+    fn hash_children(&self, children: &[Fr]) -> Fr;
+}
+
+/// circomlib `MiMCSponge(arity, 220, 1)`, one permutation per child
+impl NodeHasher for MimcSponge {
+    fn permutations(&self, arity: usize) -> Option<usize> {
+        Some(arity)
+    }
+
+    fn hash_children(&self, children: &[Fr]) -> Fr {
+        self.multi_hash_fr(children, Fr::zero(), 1)[0]
+    }
+}
+
+/// circomlib `MultiMiMC7(arity, 91)` with a zero key, one permutation per child
+impl NodeHasher for Mimc7 {
+    fn permutations(&self, arity: usize) -> Option<usize> {
+        Some(arity)
+    }
+
+    fn hash_children(&self, children: &[Fr]) -> Fr {
+        self.multi_hash_fr(children, Fr::zero())
+    }
+}
+
+/// circomlib `Poseidon(arity)`, a single permutation of width `arity + 1`
+impl NodeHasher for Poseidon {
+    fn permutations(&self, arity: usize) -> Option<usize> {
+        if arity + 1 == self.width() {
+            Some(1)
+        } else {
+            None
+        }
+    }
+
+    fn hash_children(&self, children: &[Fr]) -> Fr {
+        self.hash_fr(children)
+    }
+}
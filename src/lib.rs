@@ -1,17 +1,47 @@
 use ark_bn254::FrParameters;
 
+mod babyjub;
+mod blake256;
+pub mod convert;
+mod field;
+mod hasher;
 mod merkle_tree;
+mod mimc7;
 mod mimcsponge;
+mod multi_tree;
+mod nary_tree;
+mod nullifier_tree;
 mod ordinal_cash;
+mod pedersen;
 mod pairing;
+mod poseidon;
+mod reduction;
+mod tree_builder;
+pub mod tree_state;
 mod utils;
+mod witness;
 
 pub type U256 = ethnum::U256;
 
 pub type Address = U256;
 pub(crate) use crate::pairing::Proof;
 
+pub use babyjub::Point;
+pub use field::{Bn254Fq, Bn254Fr, FieldElement, FieldParams};
+pub use hasher::{NodeHasher, TreeHasher};
+pub use merkle_tree::{MerkleTreeWithHistory, RebuildError};
+pub use mimc7::Mimc7;
+pub use mimcsponge::{MimcSponge, Sponge};
+pub use multi_tree::{LeafPosition, MultiTreeWithHistory};
+pub use nary_tree::NaryMerkleTreeWithHistory;
+pub use nullifier_tree::{IndexedLeaf, LeafProof, NullifierTree};
 pub use ordinal_cash::{OrdinalCash, SplOrdinal};
+pub use pedersen::Pedersen;
+pub use poseidon::Poseidon;
+pub use reduction::{Barrett, Montgomery};
+pub use tree_builder::FullTree;
+pub use utils::{ct_mulmod, ct_pow, ct_select, hash_to_field, hash_to_fr, legendre, mod_inverse, mod_sqrt, FieldHash};
+pub use witness::{IncrementalWitness, WitnessSet};
 
 #[macro_export]
 macro_rules! bignum {
@@ -21,4 +51,23 @@ macro_rules! bignum {
         Box::new(U256::from_le_bytes(to_sign_and_limbs!($c0).1))
         }
     }
-}
\ No newline at end of file
+}
+/// `bignum!` evaluated at compile time, usable in `const` and `static` items.
+#[macro_export]
+macro_rules! const_bignum {
+    ($c0: expr) => {
+        {
+        use ordinal_macros::to_u256_words;
+        let (hi, lo) = to_u256_words!($c0);
+        $crate::U256::from_words(hi, lo)
+        }
+    }
+}
+
+/// BN254 scalar field element built at compile time from a decimal string.
+#[macro_export]
+macro_rules! const_fr {
+    ($c0: expr) => {
+        ark_bn254::Fr::new(ark_ff::BigInteger256::new(ordinal_macros::to_montgomery_limbs!($c0)))
+    }
+}
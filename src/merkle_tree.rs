@@ -1,64 +1,249 @@
+use ark_bn254::Fr;
+use crate::field::{Bn254Fr, FieldElement};
+use crate::hasher::TreeHasher;
 use crate::mimcsponge::MimcSponge;
-use crate::utils::addmod;
-use crate::U256;
+use crate::convert::{fr_from_u256_mod, fr_to_u256};
+use crate::{const_bignum, U256};
 #[macro_use]
 use crate::bignum;
 
-const ROOT_HISTORY_SIZE: usize = 100;
+pub const ROOT_HISTORY_SIZE: usize = 100;
+
+/// Roots of empty subtrees of height `0..=levels` under `hasher`, starting
+/// from tornado's `ZERO_VALUE`. For the standard `MimcSponge` these are the
+/// first entries of `ZEROS`.
+pub(crate) fn zero_hashes<H: TreeHasher>(hasher: &H, levels: u32) -> Vec<U256> {
+    let mut zeros = vec![ZEROS[0]];
+    for i in 0..(levels as usize) {
+        let zero = fr_from_u256_mod(&zeros[i]);
+        zeros.push(fr_to_u256(&hasher.hash_left_right(zero, zero)));
+    }
+    zeros
+}
+
+/// tornado's `zeros(i)`: `ZERO_VALUE` hashed with itself `i` times by the
+/// standard `MimcSponge`, the root of an empty subtree of height `i`.
+pub(crate) static ZEROS: [U256; 32] = [
+    const_bignum!("21663839004416932945382355908790599225266501822907911457504978515578255421292"),
+    const_bignum!("16923532097304556005972200564242292693309333953544141029519619077135960040221"),
+    const_bignum!("7833458610320835472520144237082236871909694928684820466656733259024982655488"),
+    const_bignum!("14506027710748750947258687001455876266559341618222612722926156490737302846427"),
+    const_bignum!("4766583705360062980279572762279781527342845808161105063909171241304075622345"),
+    const_bignum!("16640205414190175414380077665118269450294358858897019640557533278896634808665"),
+    const_bignum!("13024477302430254842915163302704885770955784224100349847438808884122720088412"),
+    const_bignum!("11345696205391376769769683860277269518617256738724086786512014734609753488820"),
+    const_bignum!("17235543131546745471991808272245772046758360534180976603221801364506032471936"),
+    const_bignum!("155962837046691114236524362966874066300454611955781275944230309195800494087"),
+    const_bignum!("14030416097908897320437553787826300082392928432242046897689557706485311282736"),
+    const_bignum!("12626316503845421241020584259526236205728737442715389902276517188414400172517"),
+    const_bignum!("6729873933803351171051407921027021443029157982378522227479748669930764447503"),
+    const_bignum!("12963910739953248305308691828220784129233893953613908022664851984069510335421"),
+    const_bignum!("8697310796973811813791996651816817650608143394255750603240183429036696711432"),
+    const_bignum!("9001816533475173848300051969191408053495003693097546138634479732228054209462"),
+    const_bignum!("13882856022500117449912597249521445907860641470008251408376408693167665584212"),
+    const_bignum!("6167697920744083294431071781953545901493956884412099107903554924846764168938"),
+    const_bignum!("16572499860108808790864031418434474032816278079272694833180094335573354127261"),
+    const_bignum!("11544818037702067293688063426012553693851444915243122674915303779243865603077"),
+    const_bignum!("18926336163373752588529320804722226672465218465546337267825102089394393880276"),
+    const_bignum!("11644142961923297861823153318467410719458235936926864848600378646368500787559"),
+    const_bignum!("14452740608498941570269709581566908438169321105015301708099056566809891976275"),
+    const_bignum!("7578744943370928628486790984031172450284789077258575411544517949960795417672"),
+    const_bignum!("5265560722662711931897489036950489198497887581819190855722292641626977795281"),
+    const_bignum!("731223578478205522266734242762040379509084610212963055574289967577626707020"),
+    const_bignum!("20461032451716111710758703191059719329157552073475405257510123004109059116697"),
+    const_bignum!("21109115181850306325376985763042479104020288670074922684065722930361593295700"),
+    const_bignum!("81188535419966333443828411879788371791911419113311601242851320922268145565"),
+    const_bignum!("7369375930008366466575793949976062119589129382075515225587339510228573090855"),
+    const_bignum!("14128481056524536957498216347562587505734220138697483515041882766627531681467"),
+    const_bignum!("20117374654854068065360091929240690644953205021847304657748312176352011708876"),
+];
+
+/// Why a commitment log could not be replayed into the expected tree.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RebuildError {
+    /// The log holds more leaves than the tree can.
+    TreeFull,
+    /// The log entry at position `index` carries another leaf index, i.e. an
+    /// event was missed or delivered twice.
+    IndexMismatch { index: usize, found: usize },
+    /// The log is contiguous, but does not reproduce a known root after
+    /// `index`, the leaf count of the last root that matched. With a root
+    /// for every insert this is the first divergent leaf; the log is good
+    /// before it.
+    RootMismatch { index: usize },
+}
+
+/// Undo log of everything an insert overwrites, recorded between
+/// `checkpoint` and `commit` or `rollback`.
+#[derive(Default)]
+struct Journal {
+    next_index: usize,
+    current_root_index: usize,
+    /// Old value of each level of `filled_subtrees`, first write only
+    filled_subtrees: Vec<(usize, U256)>,
+    /// Old root and leaf count of each history slot, first write only
+    roots: Vec<(usize, U256, usize)>,
+}
+
+impl Journal {
+    fn record_filled_subtree(&mut self, level: usize, old: &U256) {
+        if !self.filled_subtrees.iter().any(|(l, _)| *l == level) {
+            self.filled_subtrees.push((level, *old));
+        }
+    }
+
+    fn record_root(&mut self, slot: usize, old: &U256, old_count: usize) {
+        if !self.roots.iter().any(|(s, _, _)| *s == slot) {
+            self.roots.push((slot, *old, old_count));
+        }
+    }
+}
 
 #[derive(Default)]
-pub struct MerkleTreeWithHistory {
+pub struct MerkleTreeWithHistory<H: TreeHasher = MimcSponge> {
     pub levels: u32,
     pub filledSubtrees: Box<Vec<Box<U256>>>,
     pub zeros: Box<Vec<Box<U256>>>,
     pub currentRootIndex: usize,
     pub nextIndex: usize,
     pub roots: Box<Vec<Box<U256>>>,
+    /// Number of leaves in the tree when the root in the same slot of
+    /// `roots` was computed.
+    pub root_leaf_counts: Vec<usize>,
     pub FIELD_SIZE: Box<U256>,
     pub ZERO_VALUE: Box<U256>,
-    pub sponge: Box<MimcSponge>,
+    pub hasher: Box<H>,
+    journal: Option<Journal>,
 }
 
 impl MerkleTreeWithHistory {
     pub fn new(levels: u32) -> Self {
-        let mut this: Self = Default::default();
+        Self::with_history(levels, ROOT_HISTORY_SIZE)
+    }
+
+    /// Creates a tree that remembers the last `root_history_size` roots.
+    /// The zero hashes come from a constant table, nothing is hashed.
+    pub fn with_history(levels: u32, root_history_size: usize) -> Self {
+        Self::with_zeros(levels, root_history_size, MimcSponge::new(), &ZEROS)
+    }
+
+    /// Rebuilds a tree by replaying `leaves` in insertion order.
+    pub fn from_leaves<I: IntoIterator<Item = U256>>(levels: u32, leaves: I) -> Option<Self> {
+        let mut this = Self::new(levels);
+        let leaves: Vec<U256> = leaves.into_iter().collect();
+        this.insert_batch(&leaves)?;
+        Some(this)
+    }
+
+    /// Rebuilds a tree from a log of `(leaf_index, leaf)` deposit events and
+    /// checks it against the roots known on chain, as the `(leaf count, root)`
+    /// pairs of `roots_with_counts`. Reports the first log position that does
+    /// not match, so an indexer can refetch from there.
+    pub fn from_leaves_verified<I>(levels: u32, log: I, known_roots: &[(usize, U256)]) -> Result<Self, RebuildError>
+    where
+        I: IntoIterator<Item = (usize, U256)>,
+    {
+        let mut leaves = Vec::new();
+        for (index, (found, leaf)) in log.into_iter().enumerate() {
+            if found != index {
+                return Err(RebuildError::IndexMismatch { index, found });
+            }
+            leaves.push(leaf);
+        }
+        if leaves.len() > 2_usize.saturating_pow(levels) {
+            return Err(RebuildError::TreeFull);
+        }
+
+        let mut known_roots = known_roots.to_vec();
+        known_roots.sort_by_key(|(count, _)| *count);
+
+        // replay up to each known root in turn, so a mismatch is pinned down
+        // to the leaves since the previous one
+        let mut this = Self::new(levels);
+        let mut verified = 0;
+        for (count, root) in known_roots {
+            if count > leaves.len() {
+                // the log ends early
+                return Err(RebuildError::RootMismatch { index: verified });
+            }
+            this.insert_batch(&leaves[this.nextIndex..count]).ok_or(RebuildError::TreeFull)?;
+            if *this.getLastRoot() != root {
+                return Err(RebuildError::RootMismatch { index: verified });
+            }
+            verified = count;
+        }
+        this.insert_batch(&leaves[this.nextIndex..]).ok_or(RebuildError::TreeFull)?;
+        Ok(this)
+    }
+}
+
+impl<H: TreeHasher> MerkleTreeWithHistory<H> {
+    /// Creates a tree hashing its nodes with `hasher`.
+    pub fn with_hasher(levels: u32, root_history_size: usize, hasher: H) -> Self {
+        let zeros = zero_hashes(&hasher, levels.min(31));
+        Self::with_zeros(levels, root_history_size, hasher, &zeros)
+    }
+
+    /// `zeros[i]` is the root of an empty subtree of height `i`, for `i` up
+    /// to `levels`.
+    fn with_zeros(levels: u32, root_history_size: usize, hasher: H, zeros: &[U256]) -> Self {
+        let mut this = Self {
+            levels: 0,
+            filledSubtrees: Default::default(),
+            zeros: Default::default(),
+            currentRootIndex: 0,
+            nextIndex: 0,
+            roots: Default::default(),
+            root_leaf_counts: Vec::new(),
+            FIELD_SIZE: Default::default(),
+            ZERO_VALUE: Default::default(),
+            hasher: Box::new(hasher),
+            journal: None,
+        };
         assert!(levels > 0, "_treeLevels should be greater than zero");
         assert!(levels < 32, "_treeLevels should be less than 32");
+        assert!(root_history_size > 0, "root history should not be empty");
 
         let FIELD_SIZE = bignum!("21888242871839275222246405745257275088548364400416034343698204186575808495617");
         let ZERO_VALUE = bignum!("21663839004416932945382355908790599225266501822907911457504978515578255421292");
 
         this.levels = levels;
-        this.roots = Box::new(vec![Box::new(U256::ZERO); ROOT_HISTORY_SIZE]);
+        this.roots = Box::new(vec![Box::new(U256::ZERO); root_history_size]);
+        this.root_leaf_counts = vec![0; root_history_size];
         this.FIELD_SIZE = FIELD_SIZE.clone();
         this.ZERO_VALUE = ZERO_VALUE.clone();
 
-        let mut currentZero = ZERO_VALUE.clone();
-        this.zeros.push(currentZero.clone());
-        this.filledSubtrees.push(currentZero.clone());
-
-        for i in 1..levels {
-            currentZero = this.hashLeftRight(&currentZero, &currentZero);
-            this.zeros.push(currentZero.clone());
-            this.filledSubtrees.push(currentZero.clone());
+        for zero in &zeros[..(levels as usize)] {
+            this.zeros.push(Box::new(*zero));
+            this.filledSubtrees.push(Box::new(*zero));
         }
 
-        this.roots[0] = this.hashLeftRight(&currentZero, &currentZero);
+        *this.roots[0] = zeros[levels as usize];
         this
     }
 
     pub fn hashLeftRight(&self, left: &U256, right: &U256) -> Box<U256> {
         // assert!( left < &self.FIELD_SIZE, "_left should be inside the field");
         // assert!( right < self.FIELD_SIZE, "_right should be inside the field");
-        let R = left;
-        let C = U256::new(0);
-        let (mut R, C) = self.sponge.mimcsponge(&R, &C, &self.FIELD_SIZE);
-        R = addmod(&R, &right, &self.FIELD_SIZE);
-        let (R, C) = self.sponge.mimcsponge(&R, &C, &self.FIELD_SIZE);
-        Box::new(R)
+        let left = FieldElement::<Bn254Fr>::from_u256_mod(left);
+        let right = FieldElement::<Bn254Fr>::from_u256_mod(right);
+        let R = FieldElement::from(self.hasher.hash_left_right(left.into(), right.into()));
+        Box::new(R.value())
+    }
+
+    /// `hashLeftRight` over many independent pairs, batched by the hasher.
+    pub fn hash_pairs(&self, pairs: &[(U256, U256)]) -> Vec<U256> {
+        let pairs: Vec<(Fr, Fr)> = pairs.iter().map(|(l, r)| (fr_from_u256_mod(l), fr_from_u256_mod(r))).collect();
+        self.hasher.hash_pairs(&pairs).iter().map(fr_to_u256).collect()
     }
 
     pub fn insert(&mut self, leaf: &U256) -> Option<usize> {
+        self.insert_visit(leaf, |_, _| {})
+    }
+
+    /// `insert`, calling `visit(level, node)` with the new value of every
+    /// node on the path from the leaf (level 0) up to, excluding, the root.
+    pub(crate) fn insert_visit<F: FnMut(usize, &U256)>(&mut self, leaf: &U256, mut visit: F) -> Option<usize> {
         let mut currentIndex = self.nextIndex;
         if currentIndex == 2_usize.saturating_pow(self.levels) {
             //"Merkle tree is full. No more leafs can be added");
@@ -71,10 +256,14 @@ impl MerkleTreeWithHistory {
         let mut right: &U256;
 
         for i in 0..(self.levels as usize) {
+            visit(i, &currentLevelHash);
             if currentIndex % 2 == 0 {
                 left = &currentLevelHash;
                 right = &self.zeros[i];
 
+                if let Some(journal) = self.journal.as_mut() {
+                    journal.record_filled_subtree(i, &self.filledSubtrees[i]);
+                }
                 self.filledSubtrees[i] = currentLevelHash.clone();
             } else {
                 left = &self.filledSubtrees[i];
@@ -86,11 +275,131 @@ impl MerkleTreeWithHistory {
             currentIndex /= 2;
         }
 
-        self.currentRootIndex = (self.currentRootIndex + 1) % ROOT_HISTORY_SIZE;
-        self.roots[self.currentRootIndex] = Box::new(*currentLevelHash);
+        self.push_root(*currentLevelHash);
         Some(self.nextIndex as usize - 1)
     }
 
+    /// Inserts `leaves` level by level, hashing every internal node touched by
+    /// the batch exactly once. The resulting tree is identical to inserting the
+    /// leaves one by one with `insert`, except that only the final root is
+    /// pushed into the root history.
+    ///
+    /// Returns the index of the first inserted leaf, or `None` (leaving the
+    /// tree untouched) if the batch does not fit.
+    pub fn insert_batch(&mut self, leaves: &[U256]) -> Option<usize> {
+        let start = self.nextIndex;
+        let end = start.checked_add(leaves.len())?;
+        if end > 2_usize.saturating_pow(self.levels) {
+            return None;
+        }
+        if leaves.is_empty() {
+            return Some(start);
+        }
+
+        // `layer` holds the nodes [lo, hi) of the current level
+        let mut layer: Vec<U256> = leaves.to_vec();
+        let mut lo = start;
+        let mut hi = end;
+
+        for i in 0..(self.levels as usize) {
+            let last = hi - 1;
+            let mut pairs = Vec::with_capacity(layer.len() / 2 + 1);
+            for j in (lo / 2)..=(last / 2) {
+                let left = if 2 * j >= lo {
+                    layer[2 * j - lo]
+                } else {
+                    // The left sibling was completed by an earlier insert
+                    *self.filledSubtrees[i]
+                };
+                let right = if 2 * j + 1 < hi {
+                    layer[2 * j + 1 - lo]
+                } else {
+                    *self.zeros[i]
+                };
+                pairs.push((left, right));
+            }
+            let parents = self.hash_pairs(&pairs);
+
+            // Same value sequential insertion would leave behind: the last
+            // node at this level if it is a left child, else its left sibling
+            let filled = if last % 2 == 0 {
+                Some(layer[last - lo])
+            } else if last > lo {
+                Some(layer[last - 1 - lo])
+            } else {
+                None
+            };
+            if let Some(filled) = filled {
+                if let Some(journal) = self.journal.as_mut() {
+                    journal.record_filled_subtree(i, &self.filledSubtrees[i]);
+                }
+                *self.filledSubtrees[i] = filled;
+            }
+
+            layer = parents;
+            lo /= 2;
+            hi = last / 2 + 1;
+        }
+
+        self.nextIndex = end;
+        self.push_root(layer[0]);
+        Some(start)
+    }
+
+    fn push_root(&mut self, root: U256) {
+        self.currentRootIndex = (self.currentRootIndex + 1) % self.roots.len();
+        let slot = self.currentRootIndex;
+        if let Some(journal) = self.journal.as_mut() {
+            journal.record_root(slot, &self.roots[slot], self.root_leaf_counts[slot]);
+        }
+        *self.roots[slot] = root;
+        self.root_leaf_counts[slot] = self.nextIndex;
+    }
+
+    /// Starts recording inserts so they can be undone with `rollback`.
+    /// Checkpoints do not nest.
+    pub fn checkpoint(&mut self) {
+        assert!(self.journal.is_none(), "a checkpoint is already active");
+        self.journal = Some(Journal {
+            next_index: self.nextIndex,
+            current_root_index: self.currentRootIndex,
+            ..Default::default()
+        });
+    }
+
+    /// Keeps every insert since the last checkpoint.
+    pub fn commit(&mut self) {
+        self.journal = None;
+    }
+
+    /// Undoes every insert since the last checkpoint.
+    pub fn rollback(&mut self) {
+        let journal = self.journal.take().expect("no active checkpoint");
+        for (level, old) in journal.filled_subtrees {
+            *self.filledSubtrees[level] = old;
+        }
+        for (slot, old, old_count) in journal.roots {
+            *self.roots[slot] = old;
+            self.root_leaf_counts[slot] = old_count;
+        }
+        self.nextIndex = journal.next_index;
+        self.currentRootIndex = journal.current_root_index;
+    }
+
+    /// Like `insert_batch`, but pushes the root after every leaf into the
+    /// root history. Intermediate roots are computed on partially filled
+    /// subtrees, so this costs the same as sequential insertion.
+    pub fn insert_batch_with_history(&mut self, leaves: &[U256]) -> Option<usize> {
+        let start = self.nextIndex;
+        if start.checked_add(leaves.len())? > 2_usize.saturating_pow(self.levels) {
+            return None;
+        }
+        for leaf in leaves {
+            self.insert(leaf)?;
+        }
+        Some(start)
+    }
+
     pub fn is_known_root(&self, root: &U256) -> bool {
         if root == &U256::ZERO {
             return false;
@@ -101,7 +410,7 @@ impl MerkleTreeWithHistory {
                 return true;
             }
             if i == 0 {
-                i = ROOT_HISTORY_SIZE;
+                i = self.roots.len();
             }
 
             i -= 1;
@@ -117,12 +426,45 @@ impl MerkleTreeWithHistory {
     pub fn getLastRoot(&self) -> Box<U256> {
         self.roots[self.currentRootIndex].clone()
     }
+
+    /// Roots still in the history paired with the number of leaves the tree
+    /// held when each was computed, newest first.
+    pub fn roots_with_counts(&self) -> Vec<(usize, U256)> {
+        let len = self.roots.len();
+        (0..len)
+            .map(|k| (self.currentRootIndex + len - k) % len)
+            .filter(|&i| *self.roots[i] != U256::ZERO)
+            .map(|i| (self.root_leaf_counts[i], *self.roots[i]))
+            .collect()
+    }
+
+    /// The root right after the `n`th leaf was inserted, if it is still in
+    /// the history. Roots pushed by `insert_batch` only exist for the final
+    /// leaf count of the batch.
+    pub fn root_after(&self, n: usize) -> Option<U256> {
+        self.roots_with_counts()
+            .into_iter()
+            .find(|(count, _)| *count == n)
+            .map(|(_, root)| root)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_zeros_table() {
+        let hashed = MerkleTreeWithHistory::with_hasher(31, 1, MimcSponge::new());
+        let table = MerkleTreeWithHistory::with_history(31, 1);
+        assert_eq!(hashed.zeros, table.zeros);
+        assert_eq!(hashed.roots, table.roots);
+        assert_eq!(
+            fr_to_u256(&MimcSponge::new().hash_left_right(fr_from_u256_mod(&ZEROS[30]), fr_from_u256_mod(&ZEROS[30]))),
+            ZEROS[31],
+        );
+    }
+
     #[test]
     fn test_merkletree_new() {
         let mt = MerkleTreeWithHistory::new(16);
@@ -193,6 +535,156 @@ mod tests {
         assert!(mt.insert(&U256::new(1337)).is_none());
     }
 
+    #[test]
+    fn test_insert_batch_matches_sequential() {
+        let mut sequential = MerkleTreeWithHistory::new(3);
+        let mut batched = MerkleTreeWithHistory::new(3);
+        sequential.insert(&U256::new(1));
+        batched.insert(&U256::new(1));
+
+        let leaves: Vec<U256> = (2..7).map(U256::new).collect();
+        for leaf in &leaves {
+            sequential.insert(leaf);
+        }
+        assert_eq!(batched.insert_batch(&leaves), Some(1));
+
+        assert_eq!(batched.nextIndex, sequential.nextIndex);
+        assert_eq!(batched.getLastRoot(), sequential.getLastRoot());
+        assert_eq!(batched.filledSubtrees, sequential.filledSubtrees);
+        assert_eq!(batched.currentRootIndex, 2);
+
+        sequential.insert(&U256::new(7));
+        batched.insert(&U256::new(7));
+        assert_eq!(batched.getLastRoot(), sequential.getLastRoot());
+    }
+
+    #[test]
+    fn test_insert_batch_with_history() {
+        let mut sequential = MerkleTreeWithHistory::new(2);
+        let mut batched = MerkleTreeWithHistory::new(2);
+        let leaves = [U256::new(3), U256::new(4), U256::new(5)];
+        for leaf in &leaves {
+            sequential.insert(leaf);
+        }
+        assert_eq!(batched.insert_batch_with_history(&leaves), Some(0));
+        assert_eq!(batched.currentRootIndex, 3);
+        assert_eq!(batched.roots, sequential.roots);
+    }
+
+    #[test]
+    fn test_insert_batch_full() {
+        let mut mt = MerkleTreeWithHistory::new(2);
+        mt.insert(&U256::new(1));
+        let root = mt.getLastRoot();
+        assert!(mt.insert_batch(&[U256::new(2), U256::new(3), U256::new(4), U256::new(5)]).is_none());
+        assert_eq!(mt.nextIndex, 1);
+        assert_eq!(mt.getLastRoot(), root);
+        assert_eq!(mt.insert_batch(&[U256::new(2), U256::new(3), U256::new(4)]), Some(1));
+        assert!(mt.insert(&U256::new(5)).is_none());
+    }
+
+    #[test]
+    fn test_from_leaves() {
+        let leaves: Vec<U256> = (1..4).map(U256::new).collect();
+        let mut expected = MerkleTreeWithHistory::new(2);
+        for leaf in &leaves {
+            expected.insert(leaf);
+        }
+
+        let mt = MerkleTreeWithHistory::from_leaves(2, leaves.iter().copied()).unwrap();
+        assert_eq!(mt.nextIndex, 3);
+        assert_eq!(mt.getLastRoot(), expected.getLastRoot());
+        assert_eq!(mt.filledSubtrees, expected.filledSubtrees);
+
+        assert!(MerkleTreeWithHistory::from_leaves(1, leaves.iter().copied()).is_none());
+    }
+
+    #[test]
+    fn test_from_leaves_verified() {
+        let leaves: Vec<U256> = (1..5).map(U256::new).collect();
+        let mut on_chain = MerkleTreeWithHistory::new(2);
+        for leaf in &leaves {
+            on_chain.insert(leaf);
+        }
+        let known_roots = on_chain.roots_with_counts();
+        let root = *on_chain.getLastRoot();
+        let log: Vec<(usize, U256)> = leaves.iter().copied().enumerate().collect();
+        let verify = |levels, log: &[(usize, U256)], known_roots: &[(usize, U256)]| {
+            MerkleTreeWithHistory::from_leaves_verified(levels, log.iter().copied(), known_roots)
+        };
+
+        let mt = verify(2, &log, &known_roots).unwrap();
+        assert_eq!(*mt.getLastRoot(), root);
+        assert_eq!(*verify(2, &log, &[(4, root)]).unwrap().getLastRoot(), root);
+
+        assert_eq!(
+            verify(2, &[log[0], log[2]], &known_roots).err(),
+            Some(RebuildError::IndexMismatch { index: 1, found: 2 }),
+        );
+
+        // a wrong leaf is found by the first root after it, not the last one
+        let mut wrong = log.clone();
+        wrong[1].1 = U256::new(42);
+        assert_eq!(verify(2, &wrong, &known_roots).err(), Some(RebuildError::RootMismatch { index: 1 }));
+        assert_eq!(
+            verify(2, &wrong, &[(1, known_roots[3].1), (4, root)]).err(),
+            Some(RebuildError::RootMismatch { index: 1 }),
+        );
+        assert_eq!(verify(2, &wrong, &[(4, root)]).err(), Some(RebuildError::RootMismatch { index: 0 }));
+
+        assert_eq!(verify(2, &log[..2], &known_roots).err(), Some(RebuildError::RootMismatch { index: 2 }));
+        assert_eq!(verify(1, &log, &known_roots).err(), Some(RebuildError::TreeFull));
+    }
+
+    #[test]
+    fn test_root_after() {
+        let mut mt = MerkleTreeWithHistory::with_history(2, 3);
+        let empty_root = *mt.getLastRoot();
+        assert_eq!(mt.roots_with_counts(), vec![(0, empty_root)]);
+
+        mt.insert(&U256::new(1));
+        let first_root = *mt.getLastRoot();
+        mt.insert_batch(&[U256::new(2), U256::new(3)]);
+        let third_root = *mt.getLastRoot();
+
+        assert_eq!(mt.root_after(0), Some(empty_root));
+        assert_eq!(mt.root_after(1), Some(first_root));
+        assert_eq!(mt.root_after(2), None);
+        assert_eq!(mt.root_after(3), Some(third_root));
+
+        mt.insert(&U256::new(4));
+        assert_eq!(mt.root_after(0), None);
+        assert_eq!(
+            mt.roots_with_counts(),
+            vec![(4, *mt.getLastRoot()), (3, third_root), (1, first_root)],
+        );
+    }
+
+    #[test]
+    fn test_checkpoint_rollback() {
+        let mut mt = MerkleTreeWithHistory::with_history(2, 2);
+        mt.insert(&U256::new(1));
+        let filled = mt.filledSubtrees.clone();
+        let roots = mt.roots.clone();
+
+        mt.checkpoint();
+        mt.insert(&U256::new(2));
+        mt.insert_batch(&[U256::new(3), U256::new(4)]);
+        mt.rollback();
+
+        assert_eq!(mt.nextIndex, 1);
+        assert_eq!(mt.currentRootIndex, 1);
+        assert_eq!(mt.filledSubtrees, filled);
+        assert_eq!(mt.roots, roots);
+        assert_eq!(mt.root_after(1), Some(*roots[1]));
+
+        mt.checkpoint();
+        mt.insert(&U256::new(2));
+        mt.commit();
+        assert_eq!(mt.nextIndex, 2);
+        assert_eq!(mt.root_after(2), Some(*mt.getLastRoot()));
+    }
+
     #[test]
     fn test_is_known_root() {
         let mut mt = MerkleTreeWithHistory::new(6);
@@ -0,0 +1,128 @@
+//! https://github.com/iden3/circomlib/blob/master/src/mimc7.js
+use ark_bn254::Fr;
+use ark_ff::Field;
+use ethnum::U256;
+
+use crate::convert::{fr_from_u256_mod, fr_to_u256};
+use crate::utils::keccak_round_constants;
+
+const SEED: &str = "mimc";
+const NROUNDS: usize = 91;
+
+pub struct Mimc7 {
+    constants: Vec<Fr>,
+}
+
+impl Default for Mimc7 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Mimc7 {
+    pub fn new() -> Self {
+        Self::with_constants(&Self::get_constants(SEED, NROUNDS))
+    }
+
+    pub fn with_constants(constants: &[U256]) -> Self {
+        assert!(!constants.is_empty(), "at least one round is needed");
+        Self { constants: constants.iter().map(fr_from_u256_mod).collect() }
+    }
+
+    pub fn get_constants(seed: &str, n_rounds: usize) -> Vec<U256> {
+        keccak_round_constants(seed, n_rounds)
+    }
+
+    /// x^7 Feistel-less MiMC keyed with `k`: `t = x + k` in the first round,
+    /// `t = r + k + c_i` afterwards, `r = t^7`, output `r + k`.
+    pub fn hash_fr(&self, x_in: Fr, k: Fr) -> Fr {
+        let mut r = x_in;
+        for c in self.constants.iter() {
+            let t = r + k + c;
+            let t2 = t.square();
+            let t4 = t2.square();
+            r = t4 * t2 * t;
+        }
+        r + k
+    }
+
+    pub fn hash(&self, x_in: &U256, k: &U256) -> U256 {
+        fr_to_u256(&self.hash_fr(fr_from_u256_mod(x_in), fr_from_u256_mod(k)))
+    }
+
+    /// circomlib's `multiHash`: `r = key`, then `r = r + x_i + hash(x_i, r)`.
+    pub fn multi_hash_fr(&self, arr: &[Fr], key: Fr) -> Fr {
+        let mut r = key;
+        for x in arr {
+            r = r + x + self.hash_fr(*x, r);
+        }
+        r
+    }
+
+    pub fn multi_hash(&self, arr: &[U256], key: &U256) -> U256 {
+        let arr: Vec<Fr> = arr.iter().map(fr_from_u256_mod).collect();
+        fr_to_u256(&self.multi_hash_fr(&arr, fr_from_u256_mod(key)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bignum;
+
+    #[test]
+    fn test_constants() {
+        let constants = Mimc7::get_constants(SEED, NROUNDS);
+        assert_eq!(constants.len(), 91);
+        assert_eq!(constants[0], U256::ZERO);
+        assert_eq!(
+            constants[1],
+            *bignum!("20888961410941983456478427210666206549300505294776164667214940546594746570981"),
+        );
+    }
+
+    #[test]
+    fn test_hash() {
+        let m = Mimc7::new();
+        assert_eq!(
+            m.hash(&U256::new(1), &U256::new(2)),
+            *bignum!("10594780656576967754230020536574539122676596303354946869887184401991294982664"),
+        );
+    }
+
+    #[test]
+    fn test_multi_hash() {
+        let m = Mimc7::new();
+        let h = |arr: &[u128]| {
+            let arr: Vec<U256> = arr.iter().map(|x| U256::new(*x)).collect();
+            m.multi_hash(&arr, &U256::ZERO)
+        };
+        assert_eq!(
+            h(&[12]),
+            U256::from_str_radix("237c92644dbddb86d8a259e0e923aaab65a93f1ec5758b8799988894ac0958fd", 16).unwrap(),
+        );
+        assert_eq!(
+            h(&[78, 41]),
+            U256::from_str_radix("067f3202335ea256ae6e6aadcd2d5f7f4b06a00b2d1e0de903980d5ab552dc70", 16).unwrap(),
+        );
+        assert_eq!(
+            h(&[12, 45]),
+            U256::from_str_radix("15ff7fe9793346a17c3150804bcb36d161c8662b110c50f55ccb7113948d8879", 16).unwrap(),
+        );
+        assert_eq!(
+            h(&[12, 45, 78, 41]),
+            U256::from_str_radix("284bc1f34f335933a23a433b6ff3ee179d682cd5e5e2fcdd2d964afa85104beb", 16).unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_tree_hasher() {
+        use crate::merkle_tree::MerkleTreeWithHistory;
+        let m = Mimc7::new();
+        let mut mt = MerkleTreeWithHistory::with_hasher(1, 10, Mimc7::new());
+        mt.insert(&U256::new(12));
+        mt.insert(&U256::new(45));
+        assert_eq!(*mt.getLastRoot(), m.multi_hash(&[U256::new(12), U256::new(45)], &U256::ZERO));
+        assert_ne!(mt.getLastRoot(), MerkleTreeWithHistory::from_leaves(1, vec![U256::new(12), U256::new(45)]).unwrap().getLastRoot());
+    }
+}
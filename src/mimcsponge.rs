@@ -1,11 +1,255 @@
 ///! https://github.com/iden3/circomlib/blob/master/src/mimcsponge.js
+use ark_bn254::Fr;
+use ark_ff::{Field, Zero};
 use ethnum::U256;
-use crate::bignum;
+use std::borrow::Cow;
+use crate::{const_bignum, const_fr};
 
-use crate::utils::{addmod, mod_pow, normalize};
+use crate::field::{Bn254Fr, FieldElement, FieldParams};
+use crate::convert::{fr_from_u256, fr_from_u256_mod, fr_to_u256};
+use crate::utils::keccak_round_constants;
+
+const SEED: &str = "mimcsponge";
+const NROUNDS: usize = 220;
+/// Permutations interleaved by `mimcsponge_batch_fr`
+const LANES: usize = 4;
+
+/// `get_constants(SEED, NROUNDS)`, kept literal so that `MimcSponge::new`
+/// needs neither 220 keccaks nor any allocation
+macro_rules! round_constants {
+    ($c: ident) => {
+        [
+            $c!("0"),
+            $c!("7120861356467848435263064379192047478074060781135320967663101236819528304084"),
+            $c!("5024705281721889198577876690145313457398658950011302225525409148828000436681"),
+            $c!("17980351014018068290387269214713820287804403312720763401943303895585469787384"),
+            $c!("19886576439381707240399940949310933992335779767309383709787331470398675714258"),
+            $c!("1213715278223786725806155661738676903520350859678319590331207960381534602599"),
+            $c!("18162138253399958831050545255414688239130588254891200470934232514682584734511"),
+            $c!("7667462281466170157858259197976388676420847047604921256361474169980037581876"),
+            $c!("7207551498477838452286210989212982851118089401128156132319807392460388436957"),
+            $c!("9864183311657946807255900203841777810810224615118629957816193727554621093838"),
+            $c!("4798196928559910300796064665904583125427459076060519468052008159779219347957"),
+            $c!("17387238494588145257484818061490088963673275521250153686214197573695921400950"),
+            $c!("10005334761930299057035055370088813230849810566234116771751925093634136574742"),
+            $c!("11897542014760736209670863723231849628230383119798486487899539017466261308762"),
+            $c!("16771780563523793011283273687253985566177232886900511371656074413362142152543"),
+            $c!("749264854018824809464168489785113337925400687349357088413132714480582918506"),
+            $c!("3683645737503705042628598550438395339383572464204988015434959428676652575331"),
+            $c!("7556750851783822914673316211129907782679509728346361368978891584375551186255"),
+            $c!("20391289379084797414557439284689954098721219201171527383291525676334308303023"),
+            $c!("18146517657445423462330854383025300323335289319277199154920964274562014376193"),
+            $c!("8080173465267536232534446836148661251987053305394647905212781979099916615292"),
+            $c!("10796443006899450245502071131975731672911747129805343722228413358507805531141"),
+            $c!("5404287610364961067658660283245291234008692303120470305032076412056764726509"),
+            $c!("4623894483395123520243967718315330178025957095502546813929290333264120223168"),
+            $c!("16845753148201777192406958674202574751725237939980634861948953189320362207797"),
+            $c!("4622170486584704769521001011395820886029808520586507873417553166762370293671"),
+            $c!("16688277490485052681847773549197928630624828392248424077804829676011512392564"),
+            $c!("11878652861183667748838188993669912629573713271883125458838494308957689090959"),
+            $c!("2436445725746972287496138382764643208791713986676129260589667864467010129482"),
+            $c!("1888098689545151571063267806606510032698677328923740058080630641742325067877"),
+            $c!("148924106504065664829055598316821983869409581623245780505601526786791681102"),
+            $c!("18875020877782404439294079398043479420415331640996249745272087358069018086569"),
+            $c!("15189693413320228845990326214136820307649565437237093707846682797649429515840"),
+            $c!("19669450123472657781282985229369348220906547335081730205028099210442632534079"),
+            $c!("5521922218264623411380547905210139511350706092570900075727555783240701821773"),
+            $c!("4144769320246558352780591737261172907511489963810975650573703217887429086546"),
+            $c!("10097732913112662248360143041019433907849917041759137293018029019134392559350"),
+            $c!("1720059427972723034107765345743336447947522473310069975142483982753181038321"),
+            $c!("6302388219880227251325608388535181451187131054211388356563634768253301290116"),
+            $c!("6745410632962119604799318394592010194450845483518862700079921360015766217097"),
+            $c!("10858157235265583624235850660462324469799552996870780238992046963007491306222"),
+            $c!("20241898894740093733047052816576694435372877719072347814065227797906130857593"),
+            $c!("10165780782761211520836029617746977303303335603838343292431760011576528327409"),
+            $c!("2832093654883670345969792724123161241696170611611744759675180839473215203706"),
+            $c!("153011722355526826233082383360057587249818749719433916258246100068258954737"),
+            $c!("20196970640587451358539129330170636295243141659030208529338914906436009086943"),
+            $c!("3180973917010545328313139835982464870638521890385603025657430208141494469656"),
+            $c!("17198004293191777441573635123110935015228014028618868252989374962722329283022"),
+            $c!("7642160509228669138628515458941659189680509753651629476399516332224325757132"),
+            $c!("19346204940546791021518535594447257347218878114049998691060016493806845179755"),
+            $c!("11501810868606870391127866188394535330696206817602260610801897042898616817272"),
+            $c!("3113973447392053821824427670386252797811804954746053461397972968381571297505"),
+            $c!("6545064306297957002139416752334741502722251869537551068239642131448768236585"),
+            $c!("5203908808704813498389265425172875593837960384349653691918590736979872578408"),
+            $c!("2246692432011290582160062129070762007374502637007107318105405626910313810224"),
+            $c!("11760570435432189127645691249600821064883781677693087773459065574359292849137"),
+            $c!("5543749482491340532547407723464609328207990784853381797689466144924198391839"),
+            $c!("8837549193990558762776520822018694066937602576881497343584903902880277769302"),
+            $c!("12855514863299373699594410385788943772765811961581749194183533625311486462501"),
+            $c!("5363660674689121676875069134269386492382220935599781121306637800261912519729"),
+            $c!("13162342403579303950549728848130828093497701266240457479693991108217307949435"),
+            $c!("916941639326869583414469202910306428966657806899788970948781207501251816730"),
+            $c!("15618589556584434434009868216186115416835494805174158488636000580759692174228"),
+            $c!("8959562060028569701043973060670353733575345393653685776974948916988033453971"),
+            $c!("16390754464333401712265575949874369157699293840516802426621216808905079127650"),
+            $c!("168282396747788514908709091757591226095443902501365500003618183905496160435"),
+            $c!("8327443473179334761744301768309008451162322941906921742120510244986704677004"),
+            $c!("17213012626801210615058753489149961717422101711567228037597150941152495100640"),
+            $c!("10394369641533736715250242399198097296122982486516256408681925424076248952280"),
+            $c!("17784386835392322654196171115293700800825771210400152504776806618892170162248"),
+            $c!("16533189939837087893364000390641148516479148564190420358849587959161226782982"),
+            $c!("18725396114211370207078434315900726338547621160475533496863298091023511945076"),
+            $c!("7132325028834551397904855671244375895110341505383911719294705267624034122405"),
+            $c!("148317947440800089795933930720822493695520852448386394775371401743494965187"),
+            $c!("19001050671757720352890779127693793630251266879994702723636759889378387053056"),
+            $c!("18824274411769830274877839365728651108434404855803844568234862945613766611460"),
+            $c!("12771414330193951156383998390424063470766226667986423961689712557338777174205"),
+            $c!("11332046574800279729678603488745295198038913503395629790213378101166488244657"),
+            $c!("9607550223176946388146938069307456967842408600269548190739947540821716354749"),
+            $c!("8756385288462344550200229174435953103162307705310807828651304665320046782583"),
+            $c!("176061952957067086877570020242717222844908281373122372938833890096257042779"),
+            $c!("12200212977482648306758992405065921724409841940671166017620928947866825250857"),
+            $c!("10868453624107875516866146499877130701929063632959660262366632833504750028858"),
+            $c!("2016095394399807253596787752134573207202567875457560571095586743878953450738"),
+            $c!("21815578223768330433802113452339488275704145896544481092014911825656390567514"),
+            $c!("4923772847693564777744725640710197015181591950368494148029046443433103381621"),
+            $c!("1813584943682214789802230765734821149202472893379265320098816901270224589984"),
+            $c!("10810123816265612772922113403831964815724109728287572256602010709288980656498"),
+            $c!("1153669123397255702524721206511185557982017410156956216465120456256288427021"),
+            $c!("5007518659266430200134478928344522649876467369278722765097865662497773767152"),
+            $c!("2511432546938591792036639990606464315121646668029252285288323664350666551637"),
+            $c!("32883284540320451295484135704808083452381176816565850047310272290579727564"),
+            $c!("10484856914279112612610993418405543310546746652738541161791501150994088679557"),
+            $c!("2026733759645519472558796412979210009170379159866522399881566309631434814953"),
+            $c!("14731806221235869882801331463708736361296174006732553130708107037190460654379"),
+            $c!("14740327483193277147065845135561988641238516852487657117813536909482068950652"),
+            $c!("18787428285295558781869865751953016580493190547148386433580291216673009884554"),
+            $c!("3804047064713122820157099453648459188816376755739202017447862327783289895072"),
+            $c!("16709604795697901641948603019242067672006293290826991671766611326262532802914"),
+            $c!("11061717085931490100602849654034280576915102867237101935487893025907907250695"),
+            $c!("2821730726367472966906149684046356272806484545281639696873240305052362149654"),
+            $c!("17467794879902895769410571945152708684493991588672014763135370927880883292655"),
+            $c!("1571520786233540988201616650622796363168031165456869481368085474420849243232"),
+            $c!("10041051776251223165849354194892664881051125330236567356945669006147134614302"),
+            $c!("3981753758468103976812813304477670033098707002886030847251581853700311567551"),
+            $c!("4365864398105436789177703571412645548020537580493599380018290523813331678900"),
+            $c!("2391801327305361293476178683853802679507598622000359948432171562543560193350"),
+            $c!("214219368547551689972421167733597094823289857206402800635962137077096090722"),
+            $c!("18192064100315141084242006659317257023098826945893371479835220462302399655674"),
+            $c!("15487549757142039139328911515400805508248576685795694919457041092150651939253"),
+            $c!("10142447197759703415402259672441315777933858467700579946665223821199077641122"),
+            $c!("11246573086260753259993971254725613211193686683988426513880826148090811891866"),
+            $c!("6574066859860991369704567902211886840188702386542112593710271426704432301235"),
+            $c!("11311085442652291634822798307831431035776248927202286895207125867542470350078"),
+            $c!("20977948360215259915441258687649465618185769343138135384346964466965010873779"),
+            $c!("792781492853909872425531014397300057232399608769451037135936617996830018501"),
+            $c!("5027602491523497423798779154966735896562099398367163998686335127580757861872"),
+            $c!("14595204575654316237672764823862241845410365278802914304953002937313300553572"),
+            $c!("13973538843621261113924259058427434053808430378163734641175100160836376897004"),
+            $c!("16395063164993626722686882727042150241125309409717445381854913964674649318585"),
+            $c!("8465768840047024550750516678171433288207841931251654898809033371655109266663"),
+            $c!("21345603324471810861925019445720576814602636473739003852898308205213912255830"),
+            $c!("21171984405852590343970239018692870799717057961108910523876770029017785940991"),
+            $c!("10761027113757988230637066281488532903174559953630210849190212601991063767647"),
+            $c!("6678298831065390834922566306988418588227382406175769592902974103663687992230"),
+            $c!("4993662582188632374202316265508850988596880036291765531885657575099537176757"),
+            $c!("18364168158495573675698600238443218434246806358811328083953887470513967121206"),
+            $c!("3506345610354615013737144848471391553141006285964325596214723571988011984829"),
+            $c!("248732676202643792226973868626360612151424823368345645514532870586234380100"),
+            $c!("10090204501612803176317709245679152331057882187411777688746797044706063410969"),
+            $c!("21297149835078365363970699581821844234354988617890041296044775371855432973500"),
+            $c!("16729368143229828574342820060716366330476985824952922184463387490091156065099"),
+            $c!("4467191506765339364971058668792642195242197133011672559453028147641428433293"),
+            $c!("8677548159358013363291014307402600830078662555833653517843708051504582990832"),
+            $c!("1022951765127126818581466247360193856197472064872288389992480993218645055345"),
+            $c!("1888195070251580606973417065636430294417895423429240431595054184472931224452"),
+            $c!("4221265384902749246920810956363310125115516771964522748896154428740238579824"),
+            $c!("2825393571154632139467378429077438870179957021959813965940638905853993971879"),
+            $c!("19171031072692942278056619599721228021635671304612437350119663236604712493093"),
+            $c!("10780807212297131186617505517708903709488273075252405602261683478333331220733"),
+            $c!("18230936781133176044598070768084230333433368654744509969087239465125979720995"),
+            $c!("16901065971871379877929280081392692752968612240624985552337779093292740763381"),
+            $c!("146494141603558321291767829522948454429758543710648402457451799015963102253"),
+            $c!("2492729278659146790410698334997955258248120870028541691998279257260289595548"),
+            $c!("2204224910006646535594933495262085193210692406133533679934843341237521233504"),
+            $c!("16062117410185840274616925297332331018523844434907012275592638570193234893570"),
+            $c!("5894928453677122829055071981254202951712129328678534592916926069506935491729"),
+            $c!("4947482739415078212217504789923078546034438919537985740403824517728200332286"),
+            $c!("16143265650645676880461646123844627780378251900510645261875867423498913438066"),
+            $c!("397690828254561723549349897112473766901585444153303054845160673059519614409"),
+            $c!("11272653598912269895509621181205395118899451234151664604248382803490621227687"),
+            $c!("15566927854306879444693061574322104423426072650522411176731130806720753591030"),
+            $c!("14222898219492484180162096141564251903058269177856173968147960855133048449557"),
+            $c!("16690275395485630428127725067513114066329712673106153451801968992299636791385"),
+            $c!("3667030990325966886479548860429670833692690972701471494757671819017808678584"),
+            $c!("21280039024501430842616328642522421302481259067470872421086939673482530783142"),
+            $c!("15895485136902450169492923978042129726601461603404514670348703312850236146328"),
+            $c!("7733050956302327984762132317027414325566202380840692458138724610131603812560"),
+            $c!("438123800976401478772659663183448617575635636575786782566035096946820525816"),
+            $c!("814913922521637742587885320797606426167962526342166512693085292151314976633"),
+            $c!("12368712287081330853637674140264759478736012797026621876924395982504369598764"),
+            $c!("2494806857395134874309386694756263421445039103814920780777601708371037591569"),
+            $c!("16101132301514338989512946061786320637179843435886825102406248183507106312877"),
+            $c!("6252650284989960032925831409804233477770646333900692286731621844532438095656"),
+            $c!("9277135875276787021836189566799935097400042171346561246305113339462708861695"),
+            $c!("10493603554686607050979497281838644324893776154179810893893660722522945589063"),
+            $c!("8673089750662709235894359384294076697329948991010184356091130382437645649279"),
+            $c!("9558393272910366944245875920138649617479779893610128634419086981339060613250"),
+            $c!("19012287860122586147374214541764572282814469237161122489573881644994964647218"),
+            $c!("9783723818270121678386992630754842961728702994964214799008457449989291229500"),
+            $c!("15550788416669474113213749561488122552422887538676036667630838378023479382689"),
+            $c!("15016165746156232864069722572047169071786333815661109750860165034341572904221"),
+            $c!("6506225705710197163670556961299945987488979904603689017479840649664564978574"),
+            $c!("10796631184889302076168355684722130903785890709107732067446714470783437829037"),
+            $c!("19871836214837460419845806980869387567383718044439891735114283113359312279540"),
+            $c!("20871081766843466343749609089986071784031203517506781251203251608363835140622"),
+            $c!("5100105771517691442278432864090229416166996183792075307747582375962855820797"),
+            $c!("8777887112076272395250620301071581171386440850451972412060638225741125310886"),
+            $c!("5300440870136391278944213332144327695659161151625757537632832724102670898756"),
+            $c!("1205448543652932944633962232545707633928124666868453915721030884663332604536"),
+            $c!("5542499997310181530432302492142574333860449305424174466698068685590909336771"),
+            $c!("11028094245762332275225364962905938096659249161369092798505554939952525894293"),
+            $c!("19187314764836593118404597958543112407224947638377479622725713735224279297009"),
+            $c!("17047263688548829001253658727764731047114098556534482052135734487985276987385"),
+            $c!("19914849528178967155534624144358541535306360577227460456855821557421213606310"),
+            $c!("2929658084700714257515872921366736697080475676508114973627124569375444665664"),
+            $c!("15092262360719700162343163278648422751610766427236295023221516498310468956361"),
+            $c!("21578580340755653236050830649990190843552802306886938815497471545814130084980"),
+            $c!("1258781501221760320019859066036073675029057285507345332959539295621677296991"),
+            $c!("3819598418157732134449049289585680301176983019643974929528867686268702720163"),
+            $c!("8653175945487997845203439345797943132543211416447757110963967501177317426221"),
+            $c!("6614652990340435611114076169697104582524566019034036680161902142028967568142"),
+            $c!("19212515502973904821995111796203064175854996071497099383090983975618035391558"),
+            $c!("18664315914479294273286016871365663486061896605232511201418576829062292269769"),
+            $c!("11498264615058604317482574216318586415670903094838791165247179252175768794889"),
+            $c!("10814026414212439999107945133852431304483604215416531759535467355316227331774"),
+            $c!("17566185590731088197064706533119299946752127014428399631467913813769853431107"),
+            $c!("14016139747289624978792446847000951708158212463304817001882956166752906714332"),
+            $c!("8242601581342441750402731523736202888792436665415852106196418942315563860366"),
+            $c!("9244680976345080074252591214216060854998619670381671198295645618515047080988"),
+            $c!("12216779172735125538689875667307129262237123728082657485828359100719208190116"),
+            $c!("10702811721859145441471328511968332847175733707711670171718794132331147396634"),
+            $c!("6479667912792222539919362076122453947926362746906450079329453150607427372979"),
+            $c!("15117544653571553820496948522381772148324367479772362833334593000535648316185"),
+            $c!("6842203153996907264167856337497139692895299874139131328642472698663046726780"),
+            $c!("12732823292801537626009139514048596316076834307941224506504666470961250728055"),
+            $c!("6936272626871035740815028148058841877090860312517423346335878088297448888663"),
+            $c!("17297554111853491139852678417579991271009602631577069694853813331124433680030"),
+            $c!("16641596134749940573104316021365063031319260205559553673368334842484345864859"),
+            $c!("7400481189785154329569470986896455371037813715804007747228648863919991399081"),
+            $c!("2273205422216987330510475127669563545720586464429614439716564154166712854048"),
+            $c!("15162538063742142685306302282127534305212832649282186184583465569986719234456"),
+            $c!("5628039096440332922248578319648483863204530861778160259559031331287721255522"),
+            $c!("16085392195894691829567913404182676871326863890140775376809129785155092531260"),
+            $c!("14227467863135365427954093998621993651369686288941275436795622973781503444257"),
+            $c!("18224457394066545825553407391290108485121649197258948320896164404518684305122"),
+            $c!("274945154732293792784580363548970818611304339008964723447672490026510689427"),
+            $c!("11050822248291117548220126630860474473945266276626263036056336623671308219529"),
+            $c!("2119542016932434047340813757208803962484943912710204325088879681995922344971"),
+            $c!("0"),
+        ]
+    };
+}
+
+static CONSTANTS: [U256; NROUNDS] = round_constants!(const_bignum);
+static CONSTANTS_FR: [Fr; NROUNDS] = round_constants!(const_fr);
 
 pub struct MimcSponge {
-    constants: [Box<U256>; 220],
+    constants: Cow<'static, [U256]>,
+    /// `constants` in Montgomery form, for `mimcsponge_fr`
+    constants_fr: Cow<'static, [Fr]>,
 }
 
 impl Default for MimcSponge {
@@ -16,262 +260,296 @@ impl Default for MimcSponge {
 
 impl MimcSponge {
     pub fn new() -> Self {
-
         Self {
-            constants: [
-                bignum!("0"),
-                bignum!("7120861356467848435263064379192047478074060781135320967663101236819528304084"),
-                bignum!("5024705281721889198577876690145313457398658950011302225525409148828000436681"),
-                bignum!("17980351014018068290387269214713820287804403312720763401943303895585469787384"),
-                bignum!("19886576439381707240399940949310933992335779767309383709787331470398675714258"),
-                bignum!("1213715278223786725806155661738676903520350859678319590331207960381534602599"),
-                bignum!("18162138253399958831050545255414688239130588254891200470934232514682584734511"),
-                bignum!("7667462281466170157858259197976388676420847047604921256361474169980037581876"),
-                bignum!("7207551498477838452286210989212982851118089401128156132319807392460388436957"),
-                bignum!("9864183311657946807255900203841777810810224615118629957816193727554621093838"),
-                bignum!("4798196928559910300796064665904583125427459076060519468052008159779219347957"),
-                bignum!("17387238494588145257484818061490088963673275521250153686214197573695921400950"),
-                bignum!("10005334761930299057035055370088813230849810566234116771751925093634136574742"),
-                bignum!("11897542014760736209670863723231849628230383119798486487899539017466261308762"),
-                bignum!("16771780563523793011283273687253985566177232886900511371656074413362142152543"),
-                bignum!("749264854018824809464168489785113337925400687349357088413132714480582918506"),
-                bignum!("3683645737503705042628598550438395339383572464204988015434959428676652575331"),
-                bignum!("7556750851783822914673316211129907782679509728346361368978891584375551186255"),
-                bignum!("20391289379084797414557439284689954098721219201171527383291525676334308303023"),
-                bignum!("18146517657445423462330854383025300323335289319277199154920964274562014376193"),
-                bignum!("8080173465267536232534446836148661251987053305394647905212781979099916615292"),
-                bignum!("10796443006899450245502071131975731672911747129805343722228413358507805531141"),
-                bignum!("5404287610364961067658660283245291234008692303120470305032076412056764726509"),
-                bignum!("4623894483395123520243967718315330178025957095502546813929290333264120223168"),
-                bignum!("16845753148201777192406958674202574751725237939980634861948953189320362207797"),
-                bignum!("4622170486584704769521001011395820886029808520586507873417553166762370293671"),
-                bignum!("16688277490485052681847773549197928630624828392248424077804829676011512392564"),
-                bignum!("11878652861183667748838188993669912629573713271883125458838494308957689090959"),
-                bignum!("2436445725746972287496138382764643208791713986676129260589667864467010129482"),
-                bignum!("1888098689545151571063267806606510032698677328923740058080630641742325067877"),
-                bignum!("148924106504065664829055598316821983869409581623245780505601526786791681102"),
-                bignum!("18875020877782404439294079398043479420415331640996249745272087358069018086569"),
-                bignum!("15189693413320228845990326214136820307649565437237093707846682797649429515840"),
-                bignum!("19669450123472657781282985229369348220906547335081730205028099210442632534079"),
-                bignum!("5521922218264623411380547905210139511350706092570900075727555783240701821773"),
-                bignum!("4144769320246558352780591737261172907511489963810975650573703217887429086546"),
-                bignum!("10097732913112662248360143041019433907849917041759137293018029019134392559350"),
-                bignum!("1720059427972723034107765345743336447947522473310069975142483982753181038321"),
-                bignum!("6302388219880227251325608388535181451187131054211388356563634768253301290116"),
-                bignum!("6745410632962119604799318394592010194450845483518862700079921360015766217097"),
-                bignum!("10858157235265583624235850660462324469799552996870780238992046963007491306222"),
-                bignum!("20241898894740093733047052816576694435372877719072347814065227797906130857593"),
-                bignum!("10165780782761211520836029617746977303303335603838343292431760011576528327409"),
-                bignum!("2832093654883670345969792724123161241696170611611744759675180839473215203706"),
-                bignum!("153011722355526826233082383360057587249818749719433916258246100068258954737"),
-                bignum!("20196970640587451358539129330170636295243141659030208529338914906436009086943"),
-                bignum!("3180973917010545328313139835982464870638521890385603025657430208141494469656"),
-                bignum!("17198004293191777441573635123110935015228014028618868252989374962722329283022"),
-                bignum!("7642160509228669138628515458941659189680509753651629476399516332224325757132"),
-                bignum!("19346204940546791021518535594447257347218878114049998691060016493806845179755"),
-                bignum!("11501810868606870391127866188394535330696206817602260610801897042898616817272"),
-                bignum!("3113973447392053821824427670386252797811804954746053461397972968381571297505"),
-                bignum!("6545064306297957002139416752334741502722251869537551068239642131448768236585"),
-                bignum!("5203908808704813498389265425172875593837960384349653691918590736979872578408"),
-                bignum!("2246692432011290582160062129070762007374502637007107318105405626910313810224"),
-                bignum!("11760570435432189127645691249600821064883781677693087773459065574359292849137"),
-                bignum!("5543749482491340532547407723464609328207990784853381797689466144924198391839"),
-                bignum!("8837549193990558762776520822018694066937602576881497343584903902880277769302"),
-                bignum!("12855514863299373699594410385788943772765811961581749194183533625311486462501"),
-                bignum!("5363660674689121676875069134269386492382220935599781121306637800261912519729"),
-                bignum!("13162342403579303950549728848130828093497701266240457479693991108217307949435"),
-                bignum!("916941639326869583414469202910306428966657806899788970948781207501251816730"),
-                bignum!("15618589556584434434009868216186115416835494805174158488636000580759692174228"),
-                bignum!("8959562060028569701043973060670353733575345393653685776974948916988033453971"),
-                bignum!("16390754464333401712265575949874369157699293840516802426621216808905079127650"),
-                bignum!("168282396747788514908709091757591226095443902501365500003618183905496160435"),
-                bignum!("8327443473179334761744301768309008451162322941906921742120510244986704677004"),
-                bignum!("17213012626801210615058753489149961717422101711567228037597150941152495100640"),
-                bignum!("10394369641533736715250242399198097296122982486516256408681925424076248952280"),
-                bignum!("17784386835392322654196171115293700800825771210400152504776806618892170162248"),
-                bignum!("16533189939837087893364000390641148516479148564190420358849587959161226782982"),
-                bignum!("18725396114211370207078434315900726338547621160475533496863298091023511945076"),
-                bignum!("7132325028834551397904855671244375895110341505383911719294705267624034122405"),
-                bignum!("148317947440800089795933930720822493695520852448386394775371401743494965187"),
-                bignum!("19001050671757720352890779127693793630251266879994702723636759889378387053056"),
-                bignum!("18824274411769830274877839365728651108434404855803844568234862945613766611460"),
-                bignum!("12771414330193951156383998390424063470766226667986423961689712557338777174205"),
-                bignum!("11332046574800279729678603488745295198038913503395629790213378101166488244657"),
-                bignum!("9607550223176946388146938069307456967842408600269548190739947540821716354749"),
-                bignum!("8756385288462344550200229174435953103162307705310807828651304665320046782583"),
-                bignum!("176061952957067086877570020242717222844908281373122372938833890096257042779"),
-                bignum!("12200212977482648306758992405065921724409841940671166017620928947866825250857"),
-                bignum!("10868453624107875516866146499877130701929063632959660262366632833504750028858"),
-                bignum!("2016095394399807253596787752134573207202567875457560571095586743878953450738"),
-                bignum!("21815578223768330433802113452339488275704145896544481092014911825656390567514"),
-                bignum!("4923772847693564777744725640710197015181591950368494148029046443433103381621"),
-                bignum!("1813584943682214789802230765734821149202472893379265320098816901270224589984"),
-                bignum!("10810123816265612772922113403831964815724109728287572256602010709288980656498"),
-                bignum!("1153669123397255702524721206511185557982017410156956216465120456256288427021"),
-                bignum!("5007518659266430200134478928344522649876467369278722765097865662497773767152"),
-                bignum!("2511432546938591792036639990606464315121646668029252285288323664350666551637"),
-                bignum!("32883284540320451295484135704808083452381176816565850047310272290579727564"),
-                bignum!("10484856914279112612610993418405543310546746652738541161791501150994088679557"),
-                bignum!("2026733759645519472558796412979210009170379159866522399881566309631434814953"),
-                bignum!("14731806221235869882801331463708736361296174006732553130708107037190460654379"),
-                bignum!("14740327483193277147065845135561988641238516852487657117813536909482068950652"),
-                bignum!("18787428285295558781869865751953016580493190547148386433580291216673009884554"),
-                bignum!("3804047064713122820157099453648459188816376755739202017447862327783289895072"),
-                bignum!("16709604795697901641948603019242067672006293290826991671766611326262532802914"),
-                bignum!("11061717085931490100602849654034280576915102867237101935487893025907907250695"),
-                bignum!("2821730726367472966906149684046356272806484545281639696873240305052362149654"),
-                bignum!("17467794879902895769410571945152708684493991588672014763135370927880883292655"),
-                bignum!("1571520786233540988201616650622796363168031165456869481368085474420849243232"),
-                bignum!("10041051776251223165849354194892664881051125330236567356945669006147134614302"),
-                bignum!("3981753758468103976812813304477670033098707002886030847251581853700311567551"),
-                bignum!("4365864398105436789177703571412645548020537580493599380018290523813331678900"),
-                bignum!("2391801327305361293476178683853802679507598622000359948432171562543560193350"),
-                bignum!("214219368547551689972421167733597094823289857206402800635962137077096090722"),
-                bignum!("18192064100315141084242006659317257023098826945893371479835220462302399655674"),
-                bignum!("15487549757142039139328911515400805508248576685795694919457041092150651939253"),
-                bignum!("10142447197759703415402259672441315777933858467700579946665223821199077641122"),
-                bignum!("11246573086260753259993971254725613211193686683988426513880826148090811891866"),
-                bignum!("6574066859860991369704567902211886840188702386542112593710271426704432301235"),
-                bignum!("11311085442652291634822798307831431035776248927202286895207125867542470350078"),
-                bignum!("20977948360215259915441258687649465618185769343138135384346964466965010873779"),
-                bignum!("792781492853909872425531014397300057232399608769451037135936617996830018501"),
-                bignum!("5027602491523497423798779154966735896562099398367163998686335127580757861872"),
-                bignum!("14595204575654316237672764823862241845410365278802914304953002937313300553572"),
-                bignum!("13973538843621261113924259058427434053808430378163734641175100160836376897004"),
-                bignum!("16395063164993626722686882727042150241125309409717445381854913964674649318585"),
-                bignum!("8465768840047024550750516678171433288207841931251654898809033371655109266663"),
-                bignum!("21345603324471810861925019445720576814602636473739003852898308205213912255830"),
-                bignum!("21171984405852590343970239018692870799717057961108910523876770029017785940991"),
-                bignum!("10761027113757988230637066281488532903174559953630210849190212601991063767647"),
-                bignum!("6678298831065390834922566306988418588227382406175769592902974103663687992230"),
-                bignum!("4993662582188632374202316265508850988596880036291765531885657575099537176757"),
-                bignum!("18364168158495573675698600238443218434246806358811328083953887470513967121206"),
-                bignum!("3506345610354615013737144848471391553141006285964325596214723571988011984829"),
-                bignum!("248732676202643792226973868626360612151424823368345645514532870586234380100"),
-                bignum!("10090204501612803176317709245679152331057882187411777688746797044706063410969"),
-                bignum!("21297149835078365363970699581821844234354988617890041296044775371855432973500"),
-                bignum!("16729368143229828574342820060716366330476985824952922184463387490091156065099"),
-                bignum!("4467191506765339364971058668792642195242197133011672559453028147641428433293"),
-                bignum!("8677548159358013363291014307402600830078662555833653517843708051504582990832"),
-                bignum!("1022951765127126818581466247360193856197472064872288389992480993218645055345"),
-                bignum!("1888195070251580606973417065636430294417895423429240431595054184472931224452"),
-                bignum!("4221265384902749246920810956363310125115516771964522748896154428740238579824"),
-                bignum!("2825393571154632139467378429077438870179957021959813965940638905853993971879"),
-                bignum!("19171031072692942278056619599721228021635671304612437350119663236604712493093"),
-                bignum!("10780807212297131186617505517708903709488273075252405602261683478333331220733"),
-                bignum!("18230936781133176044598070768084230333433368654744509969087239465125979720995"),
-                bignum!("16901065971871379877929280081392692752968612240624985552337779093292740763381"),
-                bignum!("146494141603558321291767829522948454429758543710648402457451799015963102253"),
-                bignum!("2492729278659146790410698334997955258248120870028541691998279257260289595548"),
-                bignum!("2204224910006646535594933495262085193210692406133533679934843341237521233504"),
-                bignum!("16062117410185840274616925297332331018523844434907012275592638570193234893570"),
-                bignum!("5894928453677122829055071981254202951712129328678534592916926069506935491729"),
-                bignum!("4947482739415078212217504789923078546034438919537985740403824517728200332286"),
-                bignum!("16143265650645676880461646123844627780378251900510645261875867423498913438066"),
-                bignum!("397690828254561723549349897112473766901585444153303054845160673059519614409"),
-                bignum!("11272653598912269895509621181205395118899451234151664604248382803490621227687"),
-                bignum!("15566927854306879444693061574322104423426072650522411176731130806720753591030"),
-                bignum!("14222898219492484180162096141564251903058269177856173968147960855133048449557"),
-                bignum!("16690275395485630428127725067513114066329712673106153451801968992299636791385"),
-                bignum!("3667030990325966886479548860429670833692690972701471494757671819017808678584"),
-                bignum!("21280039024501430842616328642522421302481259067470872421086939673482530783142"),
-                bignum!("15895485136902450169492923978042129726601461603404514670348703312850236146328"),
-                bignum!("7733050956302327984762132317027414325566202380840692458138724610131603812560"),
-                bignum!("438123800976401478772659663183448617575635636575786782566035096946820525816"),
-                bignum!("814913922521637742587885320797606426167962526342166512693085292151314976633"),
-                bignum!("12368712287081330853637674140264759478736012797026621876924395982504369598764"),
-                bignum!("2494806857395134874309386694756263421445039103814920780777601708371037591569"),
-                bignum!("16101132301514338989512946061786320637179843435886825102406248183507106312877"),
-                bignum!("6252650284989960032925831409804233477770646333900692286731621844532438095656"),
-                bignum!("9277135875276787021836189566799935097400042171346561246305113339462708861695"),
-                bignum!("10493603554686607050979497281838644324893776154179810893893660722522945589063"),
-                bignum!("8673089750662709235894359384294076697329948991010184356091130382437645649279"),
-                bignum!("9558393272910366944245875920138649617479779893610128634419086981339060613250"),
-                bignum!("19012287860122586147374214541764572282814469237161122489573881644994964647218"),
-                bignum!("9783723818270121678386992630754842961728702994964214799008457449989291229500"),
-                bignum!("15550788416669474113213749561488122552422887538676036667630838378023479382689"),
-                bignum!("15016165746156232864069722572047169071786333815661109750860165034341572904221"),
-                bignum!("6506225705710197163670556961299945987488979904603689017479840649664564978574"),
-                bignum!("10796631184889302076168355684722130903785890709107732067446714470783437829037"),
-                bignum!("19871836214837460419845806980869387567383718044439891735114283113359312279540"),
-                bignum!("20871081766843466343749609089986071784031203517506781251203251608363835140622"),
-                bignum!("5100105771517691442278432864090229416166996183792075307747582375962855820797"),
-                bignum!("8777887112076272395250620301071581171386440850451972412060638225741125310886"),
-                bignum!("5300440870136391278944213332144327695659161151625757537632832724102670898756"),
-                bignum!("1205448543652932944633962232545707633928124666868453915721030884663332604536"),
-                bignum!("5542499997310181530432302492142574333860449305424174466698068685590909336771"),
-                bignum!("11028094245762332275225364962905938096659249161369092798505554939952525894293"),
-                bignum!("19187314764836593118404597958543112407224947638377479622725713735224279297009"),
-                bignum!("17047263688548829001253658727764731047114098556534482052135734487985276987385"),
-                bignum!("19914849528178967155534624144358541535306360577227460456855821557421213606310"),
-                bignum!("2929658084700714257515872921366736697080475676508114973627124569375444665664"),
-                bignum!("15092262360719700162343163278648422751610766427236295023221516498310468956361"),
-                bignum!("21578580340755653236050830649990190843552802306886938815497471545814130084980"),
-                bignum!("1258781501221760320019859066036073675029057285507345332959539295621677296991"),
-                bignum!("3819598418157732134449049289585680301176983019643974929528867686268702720163"),
-                bignum!("8653175945487997845203439345797943132543211416447757110963967501177317426221"),
-                bignum!("6614652990340435611114076169697104582524566019034036680161902142028967568142"),
-                bignum!("19212515502973904821995111796203064175854996071497099383090983975618035391558"),
-                bignum!("18664315914479294273286016871365663486061896605232511201418576829062292269769"),
-                bignum!("11498264615058604317482574216318586415670903094838791165247179252175768794889"),
-                bignum!("10814026414212439999107945133852431304483604215416531759535467355316227331774"),
-                bignum!("17566185590731088197064706533119299946752127014428399631467913813769853431107"),
-                bignum!("14016139747289624978792446847000951708158212463304817001882956166752906714332"),
-                bignum!("8242601581342441750402731523736202888792436665415852106196418942315563860366"),
-                bignum!("9244680976345080074252591214216060854998619670381671198295645618515047080988"),
-                bignum!("12216779172735125538689875667307129262237123728082657485828359100719208190116"),
-                bignum!("10702811721859145441471328511968332847175733707711670171718794132331147396634"),
-                bignum!("6479667912792222539919362076122453947926362746906450079329453150607427372979"),
-                bignum!("15117544653571553820496948522381772148324367479772362833334593000535648316185"),
-                bignum!("6842203153996907264167856337497139692895299874139131328642472698663046726780"),
-                bignum!("12732823292801537626009139514048596316076834307941224506504666470961250728055"),
-                bignum!("6936272626871035740815028148058841877090860312517423346335878088297448888663"),
-                bignum!("17297554111853491139852678417579991271009602631577069694853813331124433680030"),
-                bignum!("16641596134749940573104316021365063031319260205559553673368334842484345864859"),
-                bignum!("7400481189785154329569470986896455371037813715804007747228648863919991399081"),
-                bignum!("2273205422216987330510475127669563545720586464429614439716564154166712854048"),
-                bignum!("15162538063742142685306302282127534305212832649282186184583465569986719234456"),
-                bignum!("5628039096440332922248578319648483863204530861778160259559031331287721255522"),
-                bignum!("16085392195894691829567913404182676871326863890140775376809129785155092531260"),
-                bignum!("14227467863135365427954093998621993651369686288941275436795622973781503444257"),
-                bignum!("18224457394066545825553407391290108485121649197258948320896164404518684305122"),
-                bignum!("274945154732293792784580363548970818611304339008964723447672490026510689427"),
-                bignum!("11050822248291117548220126630860474473945266276626263036056336623671308219529"),
-                bignum!("2119542016932434047340813757208803962484943912710204325088879681995922344971"),
-                bignum!("0"),
-            ],
+            constants: Cow::Borrowed(&CONSTANTS),
+            constants_fr: Cow::Borrowed(&CONSTANTS_FR),
         }
     }
 
-    pub fn mimcsponge(&self, xL_in: &U256, xR_in: &U256, FIELD_SIZE: &U256) -> (U256, U256) {
-        const NROUNDS: usize = 220;
-        let mut xL = normalize(&xL_in, &FIELD_SIZE);
-        let mut xR = normalize(&xR_in, &FIELD_SIZE);
-        for i in 0..NROUNDS {
-            let c = &self.constants[i];
-            let t = if i == 0 {
-                xL
+    /// Sponge with a non-standard number of rounds, constants derived from
+    /// the standard seed. For experiments on the security/cost tradeoff only,
+    /// the result is not compatible with circomlib's `MiMCSponge`.
+    pub fn with_rounds(n_rounds: usize) -> Self {
+        Self::with_constants(&Self::get_constants(SEED, n_rounds))
+    }
+
+    /// Like circomlib's circuit, the first round adds no constant, whatever
+    /// `constants[0]` holds.
+    pub fn with_constants(constants: &[U256]) -> Self {
+        assert!(!constants.is_empty(), "at least one round is needed");
+        let mut constants_fr: Vec<Fr> = constants.iter().map(fr_from_u256_mod).collect();
+        // `mimcsponge_fe` skips it, the `Fr` paths add a zero instead
+        constants_fr[0] = Fr::zero();
+        Self { constants: Cow::Owned(constants.to_vec()), constants_fr: Cow::Owned(constants_fr) }
+    }
+
+    /// circomlib's `getConstants`: iterated keccak256 of `seed` modulo the
+    /// field, with the first and last round constants set to zero.
+    pub fn get_constants(seed: &str, n_rounds: usize) -> Vec<U256> {
+        let mut constants = keccak_round_constants(seed, n_rounds);
+        if let Some(last) = constants.last_mut() {
+            *last = U256::ZERO;
+        }
+        constants
+    }
+
+    pub fn rounds(&self) -> usize {
+        self.constants.len()
+    }
+
+    /// The round constants only exist for the BN254 scalar field, so any
+    /// other `field_size` is rejected; `mimcsponge_with_key` takes none.
+    pub fn mimcsponge(&self, xL_in: &U256, xR_in: &U256, field_size: &U256) -> (U256, U256) {
+        assert_eq!(*field_size, Bn254Fr::MODULUS, "MiMC constants are defined over the BN254 scalar field");
+        self.mimcsponge_with_key(xL_in, xR_in, &U256::ZERO)
+    }
+
+    /// circomlib's `MiMCFeistel` permutation keyed with `k`, over the BN254
+    /// scalar field.
+    pub fn mimcsponge_with_key(&self, xl_in: &U256, xr_in: &U256, k: &U256) -> (U256, U256) {
+        let (xl, xr) = self.mimcsponge_fe(
+            FieldElement::from_u256_mod(xl_in),
+            FieldElement::from_u256_mod(xr_in),
+            FieldElement::from_u256_mod(k),
+        );
+        (xl.value(), xr.value())
+    }
+
+    /// Reference permutation on `FieldElement`s: `t = xL + k (+ c_i)`, then
+    /// `(xL, xR) = (xR + t^5, xL)` except in the last round, which only
+    /// updates `xR`.
+    pub fn mimcsponge_fe(
+        &self,
+        xl_in: FieldElement<Bn254Fr>,
+        xr_in: FieldElement<Bn254Fr>,
+        k: FieldElement<Bn254Fr>,
+    ) -> (FieldElement<Bn254Fr>, FieldElement<Bn254Fr>) {
+        let nrounds = self.constants.len();
+        let five = U256::new(5);
+        let mut xl = xl_in;
+        let mut xr = xr_in;
+        for (i, c) in self.constants.iter().enumerate() {
+            let t = if i == 0 { xl + k } else { xl + k + FieldElement::from_u256_mod(c) };
+            let t5 = t.pow(&five);
+            if i < nrounds - 1 {
+                let xr_tmp = xr;
+                xr = xl;
+                xl = xr_tmp + t5;
             } else {
-                addmod(&xL, &c, &FIELD_SIZE)
-            };
-            let xR_tmp = normalize(&xR, &FIELD_SIZE);
-            // dbg!(t);
-            // dbg!(mod_pow(t, U256::new(5), FIELD_SIZE));
-            if i < (NROUNDS - 1) {
-                xR = xL;
-                xL = addmod(&xR_tmp, &mod_pow(&t, &U256::new(5), &FIELD_SIZE), &FIELD_SIZE);
+                xr = xr + t5;
+            }
+        }
+        (xl, xr)
+    }
+
+    /// `mimcsponge` over `Fr`, using Montgomery multiplication instead of the
+    /// bit-serial `mulmod`. Outputs are identical.
+    pub fn mimcsponge_fr(&self, xl_in: Fr, xr_in: Fr) -> (Fr, Fr) {
+        self.mimcsponge_with_key_fr(xl_in, xr_in, Fr::zero())
+    }
+
+    pub fn mimcsponge_with_key_fr(&self, xl_in: Fr, xr_in: Fr, k: Fr) -> (Fr, Fr) {
+        let nrounds = self.constants_fr.len();
+        let mut xl = xl_in;
+        let mut xr = xr_in;
+        for (i, c) in self.constants_fr.iter().enumerate() {
+            let t = xl + k + c;
+            let t2 = t.square();
+            let t5 = t2.square() * t;
+            if i < nrounds - 1 {
+                let xr_tmp = xr;
+                xr = xl;
+                xl = xr_tmp + t5;
             } else {
-                xR = addmod(&xR_tmp, &mod_pow(&t, &U256::new(5), &FIELD_SIZE), &FIELD_SIZE);
+                xr += t5;
             }
         }
+        (xl, xr)
+    }
+
+    /// `mimcsponge_fr` on every `(xL, xR)` in place. Rounds of `LANES`
+    /// independent permutations are interleaved so their multiplications
+    /// can overlap.
+    pub fn mimcsponge_batch_fr(&self, states: &mut [(Fr, Fr)]) {
+        let nrounds = self.constants_fr.len();
+        for lanes in states.chunks_mut(LANES) {
+            for (i, c) in self.constants_fr.iter().enumerate() {
+                for (xl, xr) in lanes.iter_mut() {
+                    let t = *xl + c;
+                    let t2 = t.square();
+                    let t5 = t2.square() * t;
+                    if i < nrounds - 1 {
+                        let xr_tmp = *xr;
+                        *xr = *xl;
+                        *xl = xr_tmp + t5;
+                    } else {
+                        *xr += t5;
+                    }
+                }
+            }
+        }
+    }
+
+    /// `multi_hash_fr(&[left, right], 0, 1)` for many pairs at once.
+    pub fn hash_pairs_fr(&self, pairs: &[(Fr, Fr)]) -> Vec<Fr> {
+        let mut states: Vec<(Fr, Fr)> = pairs.iter().map(|(left, _)| (*left, Fr::zero())).collect();
+        self.mimcsponge_batch_fr(&mut states);
+        for ((xl, _), (_, right)) in states.iter_mut().zip(pairs) {
+            *xl += right;
+        }
+        self.mimcsponge_batch_fr(&mut states);
+        states.into_iter().map(|(xl, _)| xl).collect()
+    }
+
+    pub fn hash_pairs(&self, pairs: &[(U256, U256)]) -> Vec<U256> {
+        let pairs: Vec<(Fr, Fr)> = pairs.iter().map(|(l, r)| (fr_from_u256_mod(l), fr_from_u256_mod(r))).collect();
+        self.hash_pairs_fr(&pairs).iter().map(fr_to_u256).collect()
+    }
+
+    /// circomlib's `MiMCSponge(nInputs, 220, nOutputs)` / `multiHash`: absorbs
+    /// every input into the rate element, permuting after each, then squeezes
+    /// `num_outputs` elements, permuting between them.
+    pub fn multi_hash_fr(&self, inputs: &[Fr], key: Fr, num_outputs: usize) -> Vec<Fr> {
+        let mut r = Fr::zero();
+        let mut c = Fr::zero();
+        for input in inputs {
+            r += input;
+            let (r_out, c_out) = self.mimcsponge_with_key_fr(r, c, key);
+            r = r_out;
+            c = c_out;
+        }
+
+        let mut outputs = Vec::with_capacity(num_outputs);
+        if num_outputs > 0 {
+            outputs.push(r);
+        }
+        for _ in 1..num_outputs {
+            let (r_out, c_out) = self.mimcsponge_with_key_fr(r, c, key);
+            r = r_out;
+            c = c_out;
+            outputs.push(r);
+        }
+        outputs
+    }
+
+    pub fn multi_hash(&self, inputs: &[U256], key: &U256, num_outputs: usize) -> Vec<U256> {
+        let inputs: Vec<Fr> = inputs.iter().map(fr_from_u256_mod).collect();
+        self.multi_hash_fr(&inputs, fr_from_u256_mod(key), num_outputs)
+            .iter()
+            .map(fr_to_u256)
+            .collect()
+    }
+
+    /// Streaming sponge separated from other uses by `domain`.
+    pub fn sponge(&self, domain: &U256) -> Sponge<'_> {
+        Sponge { mimc: self, r: Fr::zero(), c: fr_from_u256_mod(domain), pending: Vec::new(), in_bytes: false, squeezed: 0 }
+    }
+}
+
+/// Incremental `multiHash` over messages of any length:
+///
+/// * the capacity element starts as the domain tag, so a zero domain gives
+///   exactly `multi_hash(inputs, 0, n)` for element messages;
+/// * every absorbed element is added to the rate and followed by a
+///   permutation, elements must be below the field modulus;
+/// * bytes are buffered, so a run of `absorb_bytes` calls absorbs the same
+///   as one call with the concatenation. A run ends at the next element or
+///   the first squeeze, where `0x01` and zeros up to a multiple of 31 are
+///   appended once; the run is then packed 31 bytes per element, little
+///   endian, and permuted with key `BYTES_KEY` instead of zero, so bytes
+///   never collide with elements;
+/// * the first squeeze returns the rate, every further one permutes first;
+///   nothing can be absorbed once squeezing started.
+pub struct Sponge<'a> {
+    mimc: &'a MimcSponge,
+    r: Fr,
+    c: Fr,
+    /// bytes of the current run not absorbed yet, always fewer than 31
+    pending: Vec<u8>,
+    in_bytes: bool,
+    squeezed: usize,
+}
+
+/// Key of the permutations that absorb packed bytes.
+const BYTES_KEY: u64 = 1;
+const BYTES_PER_ELEMENT: usize = 31;
+
+impl<'a> Sponge<'a> {
+    fn permute(&mut self, input: Fr, key: Fr) {
+        let (r, c) = self.mimc.mimcsponge_with_key_fr(self.r + input, self.c, key);
+        self.r = r;
+        self.c = c;
+    }
+
+    fn absorb_chunk(&mut self, chunk: &[u8]) {
+        let mut le = [0u8; 32];
+        le[..BYTES_PER_ELEMENT].copy_from_slice(chunk);
+        let input = fr_from_u256(&U256::from_le_bytes(le)).expect("31 bytes are below the modulus");
+        self.permute(input, Fr::from(BYTES_KEY));
+    }
+
+    /// Pads and absorbs the rest of the current byte run, if any.
+    fn finish_bytes(&mut self) {
+        if !self.in_bytes {
+            return;
+        }
+        let mut last = std::mem::take(&mut self.pending);
+        last.push(1);
+        last.resize(BYTES_PER_ELEMENT, 0);
+        self.absorb_chunk(&last);
+        self.in_bytes = false;
+    }
+
+    pub fn absorb_fr(&mut self, inputs: &[Fr]) {
+        assert!(self.squeezed == 0, "cannot absorb after squeezing");
+        self.finish_bytes();
+        for input in inputs {
+            self.permute(*input, Fr::zero());
+        }
+    }
+
+    /// Fails without absorbing anything if an input is not below the field
+    /// modulus.
+    pub fn absorb(&mut self, inputs: &[U256]) -> Result<(), &'static str> {
+        let inputs: Vec<Fr> = inputs
+            .iter()
+            .map(|x| fr_from_u256(x).ok_or("input should be below the field modulus"))
+            .collect::<Result<_, _>>()?;
+        self.absorb_fr(&inputs);
+        Ok(())
+    }
+
+    pub fn absorb_bytes(&mut self, bytes: &[u8]) {
+        assert!(self.squeezed == 0, "cannot absorb after squeezing");
+        self.in_bytes = true;
+        self.pending.extend_from_slice(bytes);
+        let full = self.pending.len() / BYTES_PER_ELEMENT * BYTES_PER_ELEMENT;
+        let chunks: Vec<u8> = self.pending.drain(..full).collect();
+        for chunk in chunks.chunks(BYTES_PER_ELEMENT) {
+            self.absorb_chunk(chunk);
+        }
+    }
+
+    pub fn squeeze_fr(&mut self) -> Fr {
+        if self.squeezed == 0 {
+            self.finish_bytes();
+        } else {
+            self.permute(Fr::zero(), Fr::zero());
+        }
+        self.squeezed += 1;
+        self.r
+    }
 
-        (normalize(&xL, &FIELD_SIZE), normalize(&xR, &FIELD_SIZE))
+    pub fn squeeze(&mut self) -> U256 {
+        fr_to_u256(&self.squeeze_fr())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bignum;
+    use crate::utils::addmod;
 
     #[test]
     fn test_hasher() {
@@ -290,6 +568,202 @@ mod tests {
         );
     }
 
+    #[test]
+    #[should_panic(expected = "BN254 scalar field")]
+    fn test_hasher_other_field() {
+        let m = MimcSponge::new();
+        m.mimcsponge(&U256::new(1), &U256::new(2), &U256::new(97));
+    }
+
+    #[test]
+    fn test_hasher_fr() {
+        let m = MimcSponge::new();
+        let field_size = *bignum!("21888242871839275222246405745257275088548364400416034343698204186575808495617");
+        let inputs = [
+            (U256::new(1), U256::new(2)),
+            (U256::ZERO, U256::ZERO),
+            (*bignum!("21663839004416932945382355908790599225266501822907911457504978515578255421292"), U256::new(5)),
+            (field_size - 1, field_size + 3),
+        ];
+        for (l, r) in inputs.iter() {
+            let (xl, xr) = m.mimcsponge_fr(fr_from_u256_mod(l), fr_from_u256_mod(r));
+            assert_eq!((fr_to_u256(&xl), fr_to_u256(&xr)), m.mimcsponge(l, r, &field_size));
+        }
+    }
+
+    #[test]
+    fn test_keyed_hasher_fr() {
+        let m = MimcSponge::new();
+        let field_size = *bignum!("21888242871839275222246405745257275088548364400416034343698204186575808495617");
+        let (l, r, k) = (U256::new(1), U256::new(2), U256::new(3));
+        let (xl, xr) = m.mimcsponge_with_key_fr(fr_from_u256_mod(&l), fr_from_u256_mod(&r), fr_from_u256_mod(&k));
+        let expected = m.mimcsponge_with_key(&l, &r, &k);
+        assert_eq!((fr_to_u256(&xl), fr_to_u256(&xr)), expected);
+        assert_ne!(expected, m.mimcsponge(&l, &r, &field_size));
+    }
+
+    #[test]
+    fn test_multi_hash() {
+        let m = MimcSponge::new();
+        let field_size = *bignum!("21888242871839275222246405745257275088548364400416034343698204186575808495617");
+
+        // hashLeftRight of the tornado tree is multiHash([left, right], 0, 1)
+        let a = U256::new(5);
+        let b = *bignum!("21663839004416932945382355908790599225266501822907911457504978515578255421292");
+        let expected = *bignum!("21652272025144185891702495507858700052653521882982711576347377471507927142323");
+        assert_eq!(m.multi_hash(&[a, b], &U256::ZERO, 1), vec![expected]);
+
+        // the sponge state keeps permuting for further outputs
+        let inputs = [U256::new(1), U256::new(2), U256::new(3)];
+        let key = U256::new(7);
+        let mut r = U256::ZERO;
+        let mut c = U256::ZERO;
+        for input in inputs.iter() {
+            let s = m.mimcsponge_with_key(&addmod(&r, input, &field_size), &c, &key);
+            r = s.0;
+            c = s.1;
+        }
+        let first = r;
+        let s = m.mimcsponge_with_key(&r, &c, &key);
+        assert_eq!(m.multi_hash(&inputs, &key, 2), vec![first, s.0]);
+        assert!(m.multi_hash(&inputs, &key, 0).is_empty());
+    }
+
+    #[test]
+    fn test_hash_pairs() {
+        let m = MimcSponge::new();
+        let pairs: Vec<(U256, U256)> = (0..7).map(|i| (U256::new(i), U256::new(100 + i))).collect();
+        let expected: Vec<U256> = pairs
+            .iter()
+            .map(|(l, r)| m.multi_hash(&[*l, *r], &U256::ZERO, 1)[0])
+            .collect();
+        assert_eq!(m.hash_pairs(&pairs), expected);
+        assert!(m.hash_pairs(&[]).is_empty());
+
+        let mut states = [(fr_from_u256_mod(&U256::new(1)), fr_from_u256_mod(&U256::new(2)))];
+        m.mimcsponge_batch_fr(&mut states);
+        assert_eq!(states[0], m.mimcsponge_fr(fr_from_u256_mod(&U256::new(1)), fr_from_u256_mod(&U256::new(2))));
+    }
+
+    #[test]
+    fn test_sponge() {
+        let m = MimcSponge::new();
+        let inputs = [U256::new(1), U256::new(2), U256::new(3)];
+        let mut sponge = m.sponge(&U256::ZERO);
+        sponge.absorb(&inputs[..1]).unwrap();
+        sponge.absorb(&inputs[1..]).unwrap();
+        let outputs: Vec<U256> = (0..3).map(|_| sponge.squeeze()).collect();
+        assert_eq!(outputs, m.multi_hash(&inputs, &U256::ZERO, 3));
+
+        let mut other = m.sponge(&U256::ONE);
+        other.absorb(&inputs).unwrap();
+        assert_ne!(other.squeeze(), outputs[0]);
+
+        let field_size = Bn254Fr::MODULUS;
+        let mut sponge = m.sponge(&U256::ZERO);
+        assert!(sponge.absorb(&[U256::ONE, field_size]).is_err());
+        assert_eq!(sponge.squeeze(), U256::ZERO);
+    }
+
+    #[test]
+    fn test_sponge_bytes() {
+        let m = MimcSponge::new();
+        let hash = |bytes: &[u8]| {
+            let mut sponge = m.sponge(&U256::ZERO);
+            sponge.absorb_bytes(bytes);
+            sponge.squeeze()
+        };
+        assert_eq!(hash(b"ab"), m.multi_hash(&[U256::new(0x016261)], &U256::ONE, 1)[0]);
+        assert_ne!(hash(b"ab"), hash(b"ab\0"));
+        assert_ne!(hash(&[0; 30]), hash(&[0; 31]));
+        assert_eq!(
+            hash(&[0; 31]),
+            m.multi_hash(&[U256::ZERO, U256::ONE], &U256::ONE, 1)[0],
+        );
+    }
+
+    #[test]
+    fn test_sponge_split_bytes() {
+        let m = MimcSponge::new();
+        let hash = |parts: &[&[u8]]| {
+            let mut sponge = m.sponge(&U256::ZERO);
+            for part in parts {
+                sponge.absorb_bytes(part);
+            }
+            sponge.squeeze()
+        };
+        let long: Vec<u8> = (0..70).collect();
+        assert_eq!(hash(&[b"a", b"b"]), hash(&[b"ab"]));
+        assert_eq!(hash(&[&long[..5], &long[5..40], &[], &long[40..]]), hash(&[&long]));
+
+        // padding every call made these two the same elements
+        let mut glued = b"a\x01".to_vec();
+        glued.extend_from_slice(&[0; 29]);
+        glued.push(b'b');
+        assert_ne!(hash(&[b"a", b"b"]), hash(&[&glued]));
+
+        // an empty byte run is not the element 1
+        let mut elements = m.sponge(&U256::ZERO);
+        elements.absorb(&[U256::ONE]).unwrap();
+        assert_ne!(hash(&[b""]), elements.squeeze());
+
+        // elements end a byte run
+        let mut mixed = m.sponge(&U256::ZERO);
+        mixed.absorb_bytes(b"a");
+        mixed.absorb(&[U256::new(2)]).unwrap();
+        mixed.absorb_bytes(b"b");
+        assert_ne!(mixed.squeeze(), hash(&[b"ab"]));
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot absorb after squeezing")]
+    fn test_sponge_absorb_after_squeeze() {
+        let m = MimcSponge::new();
+        let mut sponge = m.sponge(&U256::ZERO);
+        sponge.squeeze();
+        let _ = sponge.absorb(&[U256::ONE]);
+    }
+
+    #[test]
+    fn test_constants_from_seed() {
+        let m = MimcSponge::new();
+        assert_eq!(MimcSponge::get_constants(SEED, NROUNDS), *m.constants);
+        assert_eq!(MimcSponge::with_rounds(220).constants_fr, m.constants_fr);
+    }
+
+    #[test]
+    fn test_first_constant_is_skipped() {
+        let mut constants = MimcSponge::get_constants(SEED, 8);
+        let m = MimcSponge::with_constants(&constants);
+        constants[0] = U256::new(12345);
+        let nonzero = MimcSponge::with_constants(&constants);
+
+        let (l, r, k) = (U256::new(1), U256::new(2), U256::new(3));
+        let expected = m.mimcsponge_with_key(&l, &r, &k);
+        assert_eq!(nonzero.mimcsponge_with_key(&l, &r, &k), expected);
+        let (xl, xr) = nonzero.mimcsponge_with_key_fr(fr_from_u256_mod(&l), fr_from_u256_mod(&r), fr_from_u256_mod(&k));
+        assert_eq!((fr_to_u256(&xl), fr_to_u256(&xr)), expected);
+
+        let mut states = [(fr_from_u256_mod(&l), fr_from_u256_mod(&r))];
+        nonzero.mimcsponge_batch_fr(&mut states);
+        assert_eq!(states[0], m.mimcsponge_fr(fr_from_u256_mod(&l), fr_from_u256_mod(&r)));
+    }
+
+    #[test]
+    fn test_reduced_rounds() {
+        let m = MimcSponge::with_rounds(110);
+        let field_size = *bignum!("21888242871839275222246405745257275088548364400416034343698204186575808495617");
+        assert_eq!(m.rounds(), 110);
+        assert_eq!(m.constants[109], U256::ZERO);
+        assert_eq!(m.constants[1..109], MimcSponge::new().constants[1..109]);
+
+        let (l, r) = (U256::new(1), U256::new(2));
+        let (xl, xr) = m.mimcsponge_fr(fr_from_u256_mod(&l), fr_from_u256_mod(&r));
+        let expected = m.mimcsponge(&l, &r, &field_size);
+        assert_eq!((fr_to_u256(&xl), fr_to_u256(&xr)), expected);
+        assert_ne!(expected, MimcSponge::new().mimcsponge(&l, &r, &field_size));
+    }
+
     #[test]
     fn test_hashing_tree() {
         let m = MimcSponge::new();
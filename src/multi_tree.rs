@@ -0,0 +1,184 @@
+use crate::merkle_tree::MerkleTreeWithHistory;
+use crate::U256;
+
+/// Position of a leaf in a `MultiTreeWithHistory`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LeafPosition {
+    pub tree_id: usize,
+    pub leaf_index: usize,
+}
+
+/// A sequence of equally deep `MerkleTreeWithHistory`s. When the current tree
+/// is full, the next insert opens a fresh tree instead of failing, so a pool
+/// keeps accepting deposits without a manual migration.
+///
+/// Only the newest `retained_trees` trees are kept. A full tree never gets a
+/// new root, so its last root stays valid for withdrawals for as long as the
+/// tree is retained.
+pub struct MultiTreeWithHistory {
+    pub levels: u32,
+    pub retained_trees: usize,
+    first_tree_id: usize,
+    trees: Vec<MerkleTreeWithHistory>,
+    checkpoint: Option<Checkpoint>,
+}
+
+/// What a rollback needs on top of the journal of the checkpointed tree
+struct Checkpoint {
+    tree_id: usize,
+    /// Trees dropped by rollovers since the checkpoint, oldest first
+    evicted: Vec<MerkleTreeWithHistory>,
+}
+
+impl MultiTreeWithHistory {
+    pub fn new(levels: u32, retained_trees: usize) -> Self {
+        assert!(retained_trees > 0, "at least one tree must be retained");
+        Self {
+            levels,
+            retained_trees,
+            first_tree_id: 0,
+            trees: vec![MerkleTreeWithHistory::new(levels)],
+            checkpoint: None,
+        }
+    }
+
+    pub fn insert(&mut self, leaf: &U256) -> LeafPosition {
+        if let Some(leaf_index) = self.current_tree_mut().insert(leaf) {
+            return LeafPosition { tree_id: self.current_tree_id(), leaf_index };
+        }
+
+        self.trees.push(MerkleTreeWithHistory::new(self.levels));
+        if self.trees.len() > self.retained_trees {
+            let evicted = self.trees.remove(0);
+            self.first_tree_id += 1;
+            if let Some(checkpoint) = self.checkpoint.as_mut() {
+                checkpoint.evicted.push(evicted);
+            }
+        }
+
+        let leaf_index = self.current_tree_mut()
+            .insert(leaf)
+            .expect("a fresh tree has room for a leaf");
+        LeafPosition { tree_id: self.current_tree_id(), leaf_index }
+    }
+
+    pub fn current_tree_id(&self) -> usize {
+        self.first_tree_id + self.trees.len() - 1
+    }
+
+    pub fn current_tree(&self) -> &MerkleTreeWithHistory {
+        self.trees.last().expect("there is always a current tree")
+    }
+
+    fn current_tree_mut(&mut self) -> &mut MerkleTreeWithHistory {
+        self.trees.last_mut().expect("there is always a current tree")
+    }
+
+    /// Returns the tree with the given id, if it is still retained.
+    pub fn tree(&self, tree_id: usize) -> Option<&MerkleTreeWithHistory> {
+        let i = tree_id.checked_sub(self.first_tree_id)?;
+        self.trees.get(i)
+    }
+
+    /// Returns the id of the retained tree that knows `root`, newest first.
+    pub fn find_root(&self, root: &U256) -> Option<usize> {
+        self.trees
+            .iter()
+            .rposition(|mt| mt.is_known_root(root))
+            .map(|i| self.first_tree_id + i)
+    }
+
+    pub fn is_known_root(&self, root: &U256) -> bool {
+        self.find_root(root).is_some()
+    }
+
+    pub fn last_root(&self) -> Box<U256> {
+        self.current_tree().getLastRoot()
+    }
+
+    /// Starts recording inserts so they can be undone with `rollback`, even
+    /// if they roll over into new trees. Checkpoints do not nest.
+    pub fn checkpoint(&mut self) {
+        assert!(self.checkpoint.is_none(), "a checkpoint is already active");
+        self.current_tree_mut().checkpoint();
+        self.checkpoint = Some(Checkpoint { tree_id: self.current_tree_id(), evicted: Vec::new() });
+    }
+
+    /// Keeps every insert since the last checkpoint.
+    pub fn commit(&mut self) {
+        let checkpoint = self.checkpoint.take().expect("no active checkpoint");
+        if let Some(i) = checkpoint.tree_id.checked_sub(self.first_tree_id) {
+            self.trees[i].commit();
+        }
+    }
+
+    /// Undoes every insert since the last checkpoint, including the trees
+    /// opened and dropped by rollovers.
+    pub fn rollback(&mut self) {
+        let checkpoint = self.checkpoint.take().expect("no active checkpoint");
+        self.first_tree_id -= checkpoint.evicted.len();
+        self.trees.splice(0..0, checkpoint.evicted);
+        self.trees.truncate(checkpoint.tree_id - self.first_tree_id + 1);
+        self.current_tree_mut().rollback();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rollover() {
+        let mut mt = MultiTreeWithHistory::new(1, 2);
+        assert_eq!(mt.insert(&U256::new(1)), LeafPosition { tree_id: 0, leaf_index: 0 });
+        assert_eq!(mt.insert(&U256::new(2)), LeafPosition { tree_id: 0, leaf_index: 1 });
+        let full_root = mt.last_root();
+
+        assert_eq!(mt.insert(&U256::new(3)), LeafPosition { tree_id: 1, leaf_index: 0 });
+        assert_eq!(mt.current_tree_id(), 1);
+        assert_eq!(mt.find_root(&full_root), Some(0));
+        assert_eq!(mt.find_root(&mt.last_root()), Some(1));
+        assert!(!mt.is_known_root(&U256::ZERO));
+    }
+
+    #[test]
+    fn test_retention() {
+        let mut mt = MultiTreeWithHistory::new(1, 2);
+        mt.insert(&U256::new(1));
+        mt.insert(&U256::new(2));
+        let first_root = mt.last_root();
+
+        for i in 3..6 {
+            mt.insert(&U256::new(i));
+        }
+
+        assert_eq!(mt.current_tree_id(), 2);
+        assert!(mt.tree(0).is_none());
+        assert!(mt.tree(1).is_some());
+        assert!(!mt.is_known_root(&first_root));
+    }
+
+    #[test]
+    fn test_checkpoint_rollback() {
+        let mut mt = MultiTreeWithHistory::new(1, 1);
+        mt.insert(&U256::new(1));
+        mt.insert(&U256::new(2));
+        let full_root = mt.last_root();
+
+        mt.checkpoint();
+        assert_eq!(mt.insert(&U256::new(3)), LeafPosition { tree_id: 1, leaf_index: 0 });
+        assert!(!mt.is_known_root(&full_root));
+        mt.rollback();
+
+        assert_eq!(mt.current_tree_id(), 0);
+        assert_eq!(mt.current_tree().nextIndex, 2);
+        assert_eq!(mt.last_root(), full_root);
+
+        mt.checkpoint();
+        mt.insert(&U256::new(3));
+        mt.commit();
+        assert_eq!(mt.current_tree_id(), 1);
+        mt.checkpoint();
+        mt.commit();
+    }
+}
@@ -0,0 +1,279 @@
+//! `MerkleTreeWithHistory` generalized to `arity` children per node, so a tree
+//! of the same capacity needs fewer levels. Nodes are compressed by a
+//! `NodeHasher`. The MiMC sponge absorbs the children one permutation each,
+//! which for `arity == 2` is exactly `hashLeftRight` but saves nothing for
+//! wider nodes; Poseidon of width `arity + 1` compresses a node in a single
+//! permutation, so with `arity == 4` a tree costs a quarter of the binary
+//! MiMC tree's permutations per insert.
+use crate::hasher::NodeHasher;
+use crate::mimcsponge::MimcSponge;
+use crate::convert::{fr_from_u256_mod, fr_to_u256};
+use ark_bn254::Fr;
+use crate::merkle_tree::ROOT_HISTORY_SIZE;
+use crate::{bignum, U256};
+
+pub struct NaryMerkleTreeWithHistory<H: NodeHasher = MimcSponge> {
+    pub levels: u32,
+    pub arity: usize,
+    /// `filled_subtrees[level][k]` is the last node written at position `k`
+    /// among its siblings
+    pub filled_subtrees: Vec<Vec<U256>>,
+    pub zeros: Vec<U256>,
+    pub current_root_index: usize,
+    pub next_index: usize,
+    pub roots: Vec<U256>,
+    pub root_leaf_counts: Vec<usize>,
+    hasher: H,
+}
+
+impl NaryMerkleTreeWithHistory {
+    /// A tree compressing nodes with the MiMC sponge.
+    pub fn new(levels: u32, arity: usize) -> Self {
+        Self::with_hasher(levels, arity, MimcSponge::new())
+    }
+}
+
+impl<H: NodeHasher> NaryMerkleTreeWithHistory<H> {
+    pub fn with_hasher(levels: u32, arity: usize, hasher: H) -> Self {
+        assert!(levels > 0, "_treeLevels should be greater than zero");
+        assert!(arity >= 2, "arity should be at least two");
+        assert!(
+            arity.checked_pow(levels).is_some(),
+            "tree capacity should fit in usize"
+        );
+        assert!(hasher.permutations(arity).is_some(), "hasher should support the arity");
+
+        let zero_value = *bignum!("21663839004416932945382355908790599225266501822907911457504978515578255421292");
+        let mut this = Self {
+            levels,
+            arity,
+            filled_subtrees: Vec::new(),
+            zeros: Vec::new(),
+            current_root_index: 0,
+            next_index: 0,
+            roots: vec![U256::ZERO; ROOT_HISTORY_SIZE],
+            root_leaf_counts: vec![0; ROOT_HISTORY_SIZE],
+            hasher,
+        };
+
+        let mut current_zero = zero_value;
+        for _ in 0..levels {
+            this.zeros.push(current_zero);
+            this.filled_subtrees.push(vec![current_zero; arity]);
+            current_zero = this.hash_children(&vec![current_zero; arity]);
+        }
+        this.roots[0] = current_zero;
+        this
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.arity.pow(self.levels)
+    }
+
+    /// Permutations of the hasher an insert costs, one node per level.
+    pub fn permutations_per_insert(&self) -> usize {
+        let per_node = self.hasher.permutations(self.arity).expect("checked by the constructor");
+        self.levels as usize * per_node
+    }
+
+    pub fn hash_children(&self, children: &[U256]) -> U256 {
+        let children: Vec<Fr> = children.iter().map(fr_from_u256_mod).collect();
+        fr_to_u256(&self.hasher.hash_children(&children))
+    }
+
+    pub fn insert(&mut self, leaf: &U256) -> Option<usize> {
+        let mut current_index = self.next_index;
+        if current_index == self.capacity() {
+            return None;
+        }
+
+        self.next_index += 1;
+        let mut current_level_hash = *leaf;
+        for i in 0..(self.levels as usize) {
+            let position = current_index % self.arity;
+            self.filled_subtrees[i][position] = current_level_hash;
+
+            let children: Vec<U256> = (0..self.arity)
+                .map(|k| if k <= position { self.filled_subtrees[i][k] } else { self.zeros[i] })
+                .collect();
+            current_level_hash = self.hash_children(&children);
+
+            current_index /= self.arity;
+        }
+
+        self.current_root_index = (self.current_root_index + 1) % self.roots.len();
+        self.roots[self.current_root_index] = current_level_hash;
+        self.root_leaf_counts[self.current_root_index] = self.next_index;
+        Some(self.next_index - 1)
+    }
+
+    /// Index and path of the last inserted leaf, in the layout `path_root`
+    /// takes: a depositor reads it right after its own insert. Later inserts
+    /// change the siblings to its right.
+    pub fn last_leaf_path(&self) -> Option<(usize, Vec<Vec<U256>>)> {
+        let index = self.next_index.checked_sub(1)?;
+        let mut current_index = index;
+        let path = (0..(self.levels as usize))
+            .map(|i| {
+                let position = current_index % self.arity;
+                current_index /= self.arity;
+                (0..self.arity)
+                    .filter(|k| *k != position)
+                    .map(|k| if k < position { self.filled_subtrees[i][k] } else { self.zeros[i] })
+                    .collect()
+            })
+            .collect();
+        Some((index, path))
+    }
+
+    /// Recomputes the root from a leaf, its index and, for every level, the
+    /// `arity - 1` siblings in order with the leaf's own node left out.
+    pub fn path_root(&self, leaf: &U256, index: usize, path: &[Vec<U256>]) -> Option<U256> {
+        if path.len() != self.levels as usize {
+            return None;
+        }
+        let mut node = *leaf;
+        let mut index = index;
+        for siblings in path {
+            if siblings.len() != self.arity - 1 {
+                return None;
+            }
+            let position = index % self.arity;
+            let mut children = siblings.clone();
+            children.insert(position, node);
+            node = self.hash_children(&children);
+            index /= self.arity;
+        }
+        Some(node)
+    }
+
+    pub fn is_known_root(&self, root: &U256) -> bool {
+        *root != U256::ZERO && self.roots.contains(root)
+    }
+
+    pub fn last_root(&self) -> U256 {
+        self.roots[self.current_root_index]
+    }
+
+    /// Roots still in the history with the leaf count each was computed at,
+    /// newest first.
+    pub fn roots_with_counts(&self) -> Vec<(usize, U256)> {
+        let len = self.roots.len();
+        (0..len)
+            .map(|k| (self.current_root_index + len - k) % len)
+            .filter(|&i| self.roots[i] != U256::ZERO)
+            .map(|i| (self.root_leaf_counts[i], self.roots[i]))
+            .collect()
+    }
+
+    pub fn root_after(&self, n: usize) -> Option<U256> {
+        self.roots_with_counts()
+            .into_iter()
+            .find(|(count, _)| *count == n)
+            .map(|(_, root)| root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::MerkleTreeWithHistory;
+    use crate::poseidon::Poseidon;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_binary_matches_merkle_tree() {
+        let mut nary = NaryMerkleTreeWithHistory::new(2, 2);
+        let mut mt = MerkleTreeWithHistory::new(2);
+        assert_eq!(nary.last_root(), *mt.getLastRoot());
+
+        for i in 1..4 {
+            nary.insert(&U256::new(i));
+            mt.insert(&U256::new(i));
+            assert_eq!(nary.last_root(), *mt.getLastRoot());
+        }
+    }
+
+    #[test]
+    fn test_quaternary_tree() {
+        let mut mt = NaryMerkleTreeWithHistory::new(1, 4);
+        let empty_root = mt.last_root();
+        assert_eq!(mt.insert(&U256::new(7)), Some(0));
+        assert_eq!(mt.insert(&U256::new(8)), Some(1));
+
+        let zero = mt.zeros[0];
+        let expected = mt.hash_children(&[U256::new(7), U256::new(8), zero, zero]);
+        assert_eq!(mt.last_root(), expected);
+        assert_eq!(mt.root_after(0), Some(empty_root));
+        assert!(mt.is_known_root(&expected));
+
+        let path = vec![vec![U256::new(7), zero, zero]];
+        assert_eq!(mt.path_root(&U256::new(8), 1, &path), Some(expected));
+
+        mt.insert(&U256::new(9));
+        mt.insert(&U256::new(10));
+        assert!(mt.insert(&U256::new(11)).is_none());
+        assert_eq!(mt.roots_with_counts().len(), 5);
+    }
+
+    #[test]
+    fn test_last_leaf_path() {
+        let mut mt = NaryMerkleTreeWithHistory::with_hasher(2, 4, Poseidon::new(5));
+        assert!(mt.last_leaf_path().is_none());
+        for i in 0..6 {
+            mt.insert(&U256::new(i + 1));
+            let (index, path) = mt.last_leaf_path().unwrap();
+            assert_eq!(index, i as usize);
+            assert_eq!(mt.path_root(&U256::new(i + 1), index, &path), Some(mt.last_root()));
+        }
+    }
+
+    /// Counts the nodes hashed, to check the cost model against the tree.
+    struct Counting<H> {
+        hasher: H,
+        nodes: Cell<usize>,
+    }
+
+    impl<H: NodeHasher> NodeHasher for Counting<H> {
+        fn permutations(&self, arity: usize) -> Option<usize> {
+            self.hasher.permutations(arity)
+        }
+
+        fn hash_children(&self, children: &[Fr]) -> Fr {
+            self.nodes.set(self.nodes.get() + 1);
+            self.hasher.hash_children(children)
+        }
+    }
+
+    fn insert_permutations<H: NodeHasher>(levels: u32, arity: usize, hasher: H) -> usize {
+        let mut mt = NaryMerkleTreeWithHistory::with_hasher(levels, arity, Counting { hasher, nodes: Cell::new(0) });
+        mt.hasher.nodes.set(0);
+        mt.insert(&U256::new(1));
+        let permutations = mt.hasher.nodes.get() * mt.hasher.permutations(arity).unwrap();
+        assert_eq!(permutations, mt.permutations_per_insert());
+        permutations
+    }
+
+    #[test]
+    fn test_poseidon_saves_permutations() {
+        // 2^20 leaves either way
+        let binary = insert_permutations(20, 2, MimcSponge::new());
+        let mimc = insert_permutations(10, 4, MimcSponge::new());
+        let poseidon = insert_permutations(10, 4, Poseidon::new(5));
+        assert_eq!(binary, 40);
+        assert_eq!(mimc, binary);
+        assert_eq!(poseidon, 10);
+
+        let mut mt = MerkleTreeWithHistory::new(20);
+        let mut nary = NaryMerkleTreeWithHistory::new(20, 2);
+        mt.insert(&U256::new(1));
+        nary.insert(&U256::new(1));
+        assert_eq!(nary.last_root(), *mt.getLastRoot());
+    }
+
+    #[test]
+    #[should_panic(expected = "hasher should support the arity")]
+    fn test_poseidon_width_must_match() {
+        NaryMerkleTreeWithHistory::with_hasher(2, 4, Poseidon::new(3));
+    }
+}
@@ -0,0 +1,311 @@
+//! Indexed Merkle tree of spent nullifier hashes.
+//!
+//! Leaves form a linked list sorted by value: every leaf stores its value and
+//! the index and value of the next larger leaf. Leaf 0 is a zero sentinel and
+//! a `next_value` of zero marks the end of the list. Membership is proven with
+//! the path of the leaf holding the value; non-membership with the path of the
+//! "low leaf" whose value and `next_value` bracket it.
+//!
+//! Leaves are hashed as field elements, so values must be below the field
+//! size: `x` and `x + p` would otherwise be two leaves with one commitment.
+use std::collections::BTreeMap;
+
+use crate::convert::{fr_from_u256_mod, fr_to_u256};
+use crate::field::{Bn254Fr, FieldParams};
+use crate::hasher::TreeHasher;
+use crate::merkle_tree::{zero_hashes, ZEROS};
+use crate::mimcsponge::MimcSponge;
+use crate::U256;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IndexedLeaf {
+    pub value: U256,
+    pub next_index: usize,
+    pub next_value: U256,
+}
+
+/// A leaf together with its authentication path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LeafProof {
+    pub index: usize,
+    pub leaf: IndexedLeaf,
+    pub path: Vec<U256>,
+}
+
+pub struct NullifierTree<H: TreeHasher = MimcSponge> {
+    pub levels: u32,
+    leaves: Vec<IndexedLeaf>,
+    /// leaf index by value, to find low leaves
+    sorted: BTreeMap<U256, usize>,
+    /// `layers[0]` holds the leaf hashes, missing nodes are `zeros`
+    layers: Vec<Vec<U256>>,
+    hasher: H,
+    /// roots of empty subtrees, by height
+    zeros: Vec<U256>,
+}
+
+impl NullifierTree {
+    pub fn new(levels: u32) -> Self {
+        assert!(levels < 32, "_treeLevels should be less than 32");
+        Self::with_zeros(levels, MimcSponge::new(), ZEROS[..=(levels as usize)].to_vec())
+    }
+}
+
+impl<H: TreeHasher> NullifierTree<H> {
+    pub fn with_hasher(levels: u32, hasher: H) -> Self {
+        let zeros = zero_hashes(&hasher, levels);
+        Self::with_zeros(levels, hasher, zeros)
+    }
+
+    fn with_zeros(levels: u32, hasher: H, zeros: Vec<U256>) -> Self {
+        let mut this = Self {
+            levels,
+            leaves: Vec::new(),
+            sorted: BTreeMap::new(),
+            layers: vec![Vec::new(); levels as usize + 1],
+            hasher,
+            zeros,
+        };
+        this.push_leaf(IndexedLeaf::default());
+        this
+    }
+
+    fn hash_left_right(&self, left: &U256, right: &U256) -> U256 {
+        fr_to_u256(&self.hasher.hash_left_right(fr_from_u256_mod(left), fr_from_u256_mod(right)))
+    }
+
+    pub fn leaf_hash(&self, leaf: &IndexedLeaf) -> U256 {
+        let value = self.hash_left_right(&leaf.value, &leaf.next_value);
+        self.hash_left_right(&value, &U256::new(leaf.next_index as u128))
+    }
+
+    pub fn root(&self) -> U256 {
+        self.node(self.levels as usize, 0)
+    }
+
+    pub fn contains(&self, value: &U256) -> bool {
+        self.sorted.contains_key(value)
+    }
+
+    /// Marks `value` as spent. Returns the index of its leaf.
+    pub fn insert(&mut self, value: &U256) -> Result<usize, &'static str> {
+        if *value == U256::ZERO {
+            return Err("Nullifier cannot be zero");
+        }
+        if !in_field(value) {
+            return Err("Nullifier should be inside the field");
+        }
+        if self.contains(value) {
+            return Err("The note has been already spent");
+        }
+        if self.leaves.len() == 2_usize.saturating_pow(self.levels) {
+            return Err("Nullifier tree is full");
+        }
+
+        let low_index = self.low_leaf_index(value);
+        let low = self.leaves[low_index];
+        let index = self.leaves.len();
+        self.set_leaf(low_index, IndexedLeaf { next_index: index, next_value: *value, ..low });
+        self.push_leaf(IndexedLeaf { value: *value, next_index: low.next_index, next_value: low.next_value });
+        Ok(index)
+    }
+
+    pub fn membership_proof(&self, value: &U256) -> Option<LeafProof> {
+        let index = *self.sorted.get(value)?;
+        Some(self.proof(index))
+    }
+
+    pub fn non_membership_proof(&self, value: &U256) -> Option<LeafProof> {
+        if self.contains(value) || !in_field(value) {
+            return None;
+        }
+        Some(self.proof(self.low_leaf_index(value)))
+    }
+
+    /// Checks that `value` is in the tree with root `root`.
+    pub fn verify_membership(&self, root: &U256, value: &U256, proof: &LeafProof) -> bool {
+        in_field(value) && proof.leaf.value == *value && self.path_root(proof) == Some(*root)
+    }
+
+    /// Checks that `value` is not in the tree with root `root`.
+    pub fn verify_non_membership(&self, root: &U256, value: &U256, proof: &LeafProof) -> bool {
+        let leaf = &proof.leaf;
+        // a low leaf with `next_value + p` hashes like the real one
+        let canonical = in_field(value) && in_field(&leaf.value) && in_field(&leaf.next_value);
+        let brackets = leaf.value < *value && (leaf.next_value == U256::ZERO || *value < leaf.next_value);
+        canonical && brackets && self.path_root(proof) == Some(*root)
+    }
+
+    /// `None` unless the path has one sibling per level and the index fits:
+    /// bits above `levels` would be ignored, `index + 2^levels` would verify
+    /// like `index`.
+    fn path_root(&self, proof: &LeafProof) -> Option<U256> {
+        if proof.path.len() != self.levels as usize || proof.index >= 2_usize.saturating_pow(self.levels) {
+            return None;
+        }
+        let mut node = self.leaf_hash(&proof.leaf);
+        for (i, sibling) in proof.path.iter().enumerate() {
+            node = if (proof.index >> i) & 1 == 1 {
+                self.hash_left_right(sibling, &node)
+            } else {
+                self.hash_left_right(&node, sibling)
+            };
+        }
+        Some(node)
+    }
+
+    fn low_leaf_index(&self, value: &U256) -> usize {
+        // the sentinel holds zero, so there always is a smaller value
+        *self.sorted.range(..*value).next_back().expect("sentinel leaf").1
+    }
+
+    fn proof(&self, index: usize) -> LeafProof {
+        let path = (0..(self.levels as usize))
+            .map(|i| self.node(i, (index >> i) ^ 1))
+            .collect();
+        LeafProof { index, leaf: self.leaves[index], path }
+    }
+
+    fn node(&self, level: usize, index: usize) -> U256 {
+        match self.layers[level].get(index) {
+            Some(node) => *node,
+            None => self.zeros[level],
+        }
+    }
+
+    fn push_leaf(&mut self, leaf: IndexedLeaf) {
+        let index = self.leaves.len();
+        self.leaves.push(leaf);
+        self.sorted.insert(leaf.value, index);
+        self.layers[0].push(U256::ZERO);
+        self.set_leaf(index, leaf);
+    }
+
+    fn set_leaf(&mut self, index: usize, leaf: IndexedLeaf) {
+        self.leaves[index] = leaf;
+        let mut node = self.leaf_hash(&leaf);
+        let mut index = index;
+        for level in 0..(self.levels as usize) {
+            if index == self.layers[level].len() {
+                self.layers[level].push(node);
+            } else {
+                self.layers[level][index] = node;
+            }
+            let sibling = self.node(level, index ^ 1);
+            node = if index % 2 == 1 {
+                self.hash_left_right(&sibling, &node)
+            } else {
+                self.hash_left_right(&node, &sibling)
+            };
+            index /= 2;
+        }
+        if self.layers[self.levels as usize].is_empty() {
+            self.layers[self.levels as usize].push(node);
+        } else {
+            self.layers[self.levels as usize][0] = node;
+        }
+    }
+}
+
+fn in_field(value: &U256) -> bool {
+    *value < Bn254Fr::MODULUS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::MerkleTreeWithHistory;
+    use crate::poseidon::Poseidon;
+
+    #[test]
+    fn test_insert_and_prove() {
+        let mut nt = NullifierTree::new(2);
+        assert_eq!(nt.insert(&U256::new(30)), Ok(1));
+        assert_eq!(nt.insert(&U256::new(10)), Ok(2));
+        assert!(nt.insert(&U256::new(10)).is_err());
+        assert!(nt.insert(&U256::ZERO).is_err());
+
+        let root = nt.root();
+        let proof = nt.membership_proof(&U256::new(10)).unwrap();
+        assert_eq!(proof.leaf, IndexedLeaf { value: U256::new(10), next_index: 1, next_value: U256::new(30) });
+        assert!(nt.verify_membership(&root, &U256::new(10), &proof));
+        assert!(!nt.verify_membership(&root, &U256::new(30), &proof));
+
+        let proof = nt.non_membership_proof(&U256::new(20)).unwrap();
+        assert_eq!(proof.index, 2);
+        assert!(nt.verify_non_membership(&root, &U256::new(20), &proof));
+        assert!(!nt.verify_non_membership(&root, &U256::new(40), &proof));
+
+        let proof = nt.non_membership_proof(&U256::new(40)).unwrap();
+        assert_eq!(proof.index, 1);
+        assert!(nt.verify_non_membership(&root, &U256::new(40), &proof));
+        assert!(nt.non_membership_proof(&U256::new(30)).is_none());
+
+        assert_eq!(nt.insert(&U256::new(20)), Ok(3));
+        assert!(!nt.verify_non_membership(&nt.root(), &U256::new(20), &proof));
+        assert_eq!(nt.insert(&U256::new(40)), Err("Nullifier tree is full"));
+    }
+
+    #[test]
+    fn test_index_out_of_range() {
+        let mut nt = NullifierTree::new(2);
+        nt.insert(&U256::new(10)).unwrap();
+        let root = nt.root();
+
+        // the same path bits with an extra high bit
+        let mut proof = nt.membership_proof(&U256::new(10)).unwrap();
+        proof.index += 4;
+        assert!(!nt.verify_membership(&root, &U256::new(10), &proof));
+        let mut proof = nt.non_membership_proof(&U256::new(20)).unwrap();
+        assert!(nt.verify_non_membership(&root, &U256::new(20), &proof));
+        proof.index += 4;
+        assert!(!nt.verify_non_membership(&root, &U256::new(20), &proof));
+    }
+
+    #[test]
+    fn test_other_hasher() {
+        let mut nt = NullifierTree::with_hasher(2, Poseidon::new(3));
+        nt.insert(&U256::new(5)).unwrap();
+        let root = nt.root();
+        assert_ne!(root, NullifierTree::new(2).root());
+        let proof = nt.membership_proof(&U256::new(5)).unwrap();
+        assert!(nt.verify_membership(&root, &U256::new(5), &proof));
+        assert_eq!(NullifierTree::with_hasher(3, MimcSponge::new()).root(), NullifierTree::new(3).root());
+    }
+
+    #[test]
+    fn test_root_matches_append_only_tree() {
+        // With no updates to earlier leaves, the layout is an append-only tree
+        let mut nt = NullifierTree::new(2);
+        nt.insert(&U256::new(5)).unwrap();
+
+        let mut mt = MerkleTreeWithHistory::new(2);
+        mt.insert(&nt.leaf_hash(&nt.leaves[0]));
+        mt.insert(&nt.leaf_hash(&nt.leaves[1]));
+        assert_eq!(nt.root(), *mt.getLastRoot());
+    }
+
+    #[test]
+    fn test_values_outside_field() {
+        let p = Bn254Fr::MODULUS;
+        let mut nt = NullifierTree::new(2);
+        assert_eq!(nt.insert(&(p + 10)), Err("Nullifier should be inside the field"));
+        assert!(nt.insert(&p).is_err());
+        assert_eq!(nt.insert(&U256::new(10)), Ok(1));
+        let root = nt.root();
+
+        let mut lifted = nt.membership_proof(&U256::new(10)).unwrap();
+        lifted.leaf.value += p;
+        assert!(!nt.verify_membership(&root, &(p + 10), &lifted));
+        assert!(nt.non_membership_proof(&(p + 5)).is_none());
+
+        // the sentinel with `next_value` lifted by p commits to the same leaf
+        // and would bracket the spent 10
+        let mut forged = nt.non_membership_proof(&U256::new(5)).unwrap();
+        assert_eq!(forged.index, 0);
+        forged.leaf.next_value += p;
+        assert_eq!(nt.leaf_hash(&forged.leaf), nt.leaf_hash(&nt.leaves[0]));
+        assert!(!nt.verify_non_membership(&root, &U256::new(10), &forged));
+        assert!(!nt.verify_non_membership(&root, &(p + 20), &nt.non_membership_proof(&U256::new(20)).unwrap()));
+    }
+}
@@ -1,15 +1,21 @@
 use std::collections::HashMap;
 use ark_ff::{field_new};
-use ark_bn254::{Fr, Fq, Fq2, G1Affine, G2Affine};
-use crate::{merkle_tree::MerkleTreeWithHistory, U256, Proof, bignum};
+use ark_bn254::{Fq, Fq2, G1Affine, G2Affine};
+use crate::{multi_tree::{LeafPosition, MultiTreeWithHistory}, U256, Proof, bignum};
+use crate::convert::fr_from_u256;
 
 pub trait Ordinal {
-    fn process_deposit(&mut self, commitment: U256, inserted_index: usize);
+    /// `position` tells the trees apart once the first one is full, e.g. for
+    /// the deposit event.
+    fn process_deposit(&mut self, commitment: U256, position: LeafPosition) -> Result<(), &'static str>;
     fn process_withdraw(&mut self, recipient: crate::Address, relayer: crate::Address, fee: U256, refund: U256);
 }
 
+/// Trees whose roots stay valid for withdrawals after they fill up
+pub const RETAINED_TREES: usize = 8;
+
 pub struct OrdinalCash<O: Ordinal> {
-    pub mt: MerkleTreeWithHistory,
+    pub mt: MultiTreeWithHistory,
     commitments: HashMap<U256, bool>,
     nullifier_hashes: HashMap<U256, bool>,
     o: O,
@@ -17,22 +23,33 @@ pub struct OrdinalCash<O: Ordinal> {
 
 impl<O: Ordinal> OrdinalCash<O> {
     pub fn new(levels: u32, o: O) -> Self {
-        let mt = MerkleTreeWithHistory::new(levels);
+        Self::with_retained_trees(levels, RETAINED_TREES, o)
+    }
+
+    pub fn with_retained_trees(levels: u32, retained_trees: usize, o: O) -> Self {
+        let mt = MultiTreeWithHistory::new(levels, retained_trees);
         let commitments = HashMap::new();
         let nullifier_hashes = HashMap::new();
 
         Self { mt, commitments, nullifier_hashes, o }
     }
 
-    pub fn deposit(&mut self, commitment: U256) -> Option<()> {
+    /// A full tree is never an error, the deposit goes into a fresh one.
+    pub fn deposit(&mut self, commitment: U256) -> Option<LeafPosition> {
         if self.commitments.contains_key(&commitment) {
             // The commitment already exists
             None
         } else {
-            let inserted_index = self.mt.insert(&commitment)?;
+            self.mt.checkpoint();
+            let position = self.mt.insert(&commitment);
+            if self.o.process_deposit(commitment, position).is_err() {
+                // e.g. the token transfer failed, leave no trace in the tree
+                self.mt.rollback();
+                return None;
+            }
+            self.mt.commit();
             self.commitments.insert(commitment, true);
-            self.o.process_deposit(commitment, inserted_index);
-            Some(())
+            Some(position)
         }
     }
 
@@ -43,12 +60,12 @@ impl<O: Ordinal> OrdinalCash<O> {
         } else if !self.mt.is_known_root(&root) {
             Err("Cannot find your merkle root")
         } else if !crate::pairing::verify_proof(proof, &[
-                to_fr(&root)?,
-                to_fr(&nh)?,
-                to_fr(&recipient)?,
-                to_fr(&relayer)?,
-                to_fr(&fee)?,
-                to_fr(&refund)?
+                fr_from_u256(root).ok_or("cannot read bytes")?,
+                fr_from_u256(nh).ok_or("cannot read bytes")?,
+                fr_from_u256(recipient).ok_or("cannot read bytes")?,
+                fr_from_u256(relayer).ok_or("cannot read bytes")?,
+                fr_from_u256(fee).ok_or("cannot read bytes")?,
+                fr_from_u256(refund).ok_or("cannot read bytes")?
             ]) {
             Err("Invalid withdraw proof")
         } else {
@@ -60,18 +77,13 @@ impl<O: Ordinal> OrdinalCash<O> {
     }
 }
 
-pub fn to_fr(e: &U256) -> Result<ark_bn254::Fr, &'static str> {
-    use ark_ff::FromBytes;
-    let bytes = e.to_le_bytes();
-    Fr::read(bytes.as_ref()).map_err(|_| "cannot read bytes")
-}
-
 pub struct SplOrdinal;
 
 impl Ordinal for SplOrdinal {
-    fn process_deposit(&mut self, commitment: U256, inserted_index: usize) {
+    fn process_deposit(&mut self, commitment: U256, position: LeafPosition) -> Result<(), &'static str> {
         //
-        dbg!(inserted_index);
+        dbg!(position);
+        Ok(())
     }
     fn process_withdraw(&mut self, recipient: crate::Address, relayer: crate::Address, fee: U256, refund: U256) {
         //
@@ -87,8 +99,8 @@ mod tests {
     fn test_deposit() {
         let levels = 2;
         let mut o = OrdinalCash::new(levels, SplOrdinal);
-        assert!(o.deposit(U256::ONE).is_some());
-        assert!(o.deposit(U256::ZERO).is_some());
+        assert_eq!(o.deposit(U256::ONE), Some(LeafPosition { tree_id: 0, leaf_index: 0 }));
+        assert_eq!(o.deposit(U256::ZERO), Some(LeafPosition { tree_id: 0, leaf_index: 1 }));
     }
 
     #[test]
@@ -99,12 +111,106 @@ mod tests {
         assert!(o.deposit(U256::ONE).is_none());
     }
 
+    struct FailingOrdinal {
+        fail: bool,
+    }
+
+    impl Ordinal for FailingOrdinal {
+        fn process_deposit(&mut self, _commitment: U256, _position: LeafPosition) -> Result<(), &'static str> {
+            if self.fail {
+                Err("transfer failed")
+            } else {
+                Ok(())
+            }
+        }
+        fn process_withdraw(&mut self, _recipient: crate::Address, _relayer: crate::Address, _fee: U256, _refund: U256) {}
+    }
+
+    #[test]
+    fn test_deposit_rolls_back_failed_hook() {
+        let levels = 2;
+        let mut o = OrdinalCash::new(levels, FailingOrdinal { fail: true });
+        let root = o.mt.last_root();
+        assert!(o.deposit(U256::ONE).is_none());
+        assert_eq!(o.mt.current_tree().nextIndex, 0);
+        assert_eq!(o.mt.current_tree().currentRootIndex, 0);
+        assert_eq!(o.mt.last_root(), root);
+
+        o.o.fail = false;
+        assert!(o.deposit(U256::ONE).is_some());
+        assert_eq!(o.mt.current_tree().nextIndex, 1);
+    }
+
+    #[test]
+    fn test_deposit_rolls_back_failed_hook_after_rollover() {
+        let mut o = OrdinalCash::with_retained_trees(1, 1, FailingOrdinal { fail: false });
+        o.deposit(U256::new(1));
+        o.deposit(U256::new(2));
+        let full_root = o.mt.last_root();
+
+        o.o.fail = true;
+        assert!(o.deposit(U256::new(3)).is_none());
+        assert_eq!(o.mt.current_tree_id(), 0);
+        assert!(o.mt.is_known_root(&full_root));
+    }
+
+    struct RecordingOrdinal {
+        deposits: Vec<(U256, LeafPosition)>,
+    }
+
+    impl Ordinal for RecordingOrdinal {
+        fn process_deposit(&mut self, commitment: U256, position: LeafPosition) -> Result<(), &'static str> {
+            self.deposits.push((commitment, position));
+            Ok(())
+        }
+        fn process_withdraw(&mut self, _recipient: crate::Address, _relayer: crate::Address, _fee: U256, _refund: U256) {}
+    }
+
+    #[test]
+    fn test_deposit_hook_sees_tree_id() {
+        let mut o = OrdinalCash::new(1, RecordingOrdinal { deposits: Vec::new() });
+        for i in 0..3 {
+            o.deposit(U256::new(i + 1));
+        }
+        assert_eq!(
+            o.o.deposits,
+            vec![
+                (U256::new(1), LeafPosition { tree_id: 0, leaf_index: 0 }),
+                (U256::new(2), LeafPosition { tree_id: 0, leaf_index: 1 }),
+                (U256::new(3), LeafPosition { tree_id: 1, leaf_index: 0 }),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_withdraw_from_full_tree() {
+        use ark_ec::AffineCurve;
+        let mut o = OrdinalCash::new(2, SplOrdinal);
+        for i in 0..4 {
+            assert_eq!(o.deposit(U256::new(i + 1)), Some(LeafPosition { tree_id: 0, leaf_index: i as usize }));
+        }
+        let full_root = o.mt.last_root();
+        assert_eq!(o.deposit(U256::new(5)), Some(LeafPosition { tree_id: 1, leaf_index: 0 }));
+
+        // the proof is for another note, so only getting past the root check
+        // shows that the full tree still counts
+        let g1 = G1Affine::prime_subgroup_generator();
+        let proof = || Proof { a: g1, b: G2Affine::prime_subgroup_generator(), c: g1 };
+        let withdraw = |o: &mut OrdinalCash<SplOrdinal>, root: &U256| {
+            o.withdraw(proof(), root, &U256::ONE, &U256::ONE, &U256::ONE, &U256::ZERO, &U256::ZERO)
+        };
+        assert_eq!(withdraw(&mut o, &full_root), Err("Invalid withdraw proof"));
+        let current_root = o.mt.last_root();
+        assert_eq!(withdraw(&mut o, &current_root), Err("Invalid withdraw proof"));
+        assert_eq!(withdraw(&mut o, &U256::new(42)), Err("Cannot find your merkle root"));
+    }
+
     #[test]
     fn test_u256_to_fr() {
-        use ark_ff::PrimeField;
+        use ark_bn254::Fr;
         assert_eq!(
             field_new!(Fr, "18141211044530898481780712096785380507009040886197825359491225784587697908689"),
-            to_fr(&*bignum!("18141211044530898481780712096785380507009040886197825359491225784587697908689")).unwrap(),
+            fr_from_u256(&*bignum!("18141211044530898481780712096785380507009040886197825359491225784587697908689")).unwrap(),
         );
     }
 
@@ -0,0 +1,188 @@
+//! https://github.com/iden3/circomlib/blob/master/src/pedersenHash.js
+//!
+//! Tornado notes are a 31 byte nullifier and a 31 byte secret. The commitment
+//! is `pedersenHash(nullifier || secret)` and the nullifier hash
+//! `pedersenHash(nullifier)`, both taken as the `x` coordinate of the point.
+//! Preimages are secret, so hashing builds the segment scalars with `subtle`
+//! selection, multiplies with `Point::mul_scalar_ct`, sums in projective
+//! coordinates and inverts once with `ct_pow`: no branches or table lookups
+//! on preimage bits. The field arithmetic underneath is arkworks' and not
+//! constant time at the instruction level.
+use ethnum::U256;
+use subtle::Choice;
+
+use crate::babyjub::{sub_order, Point, Projective};
+use crate::blake256::blake256;
+use crate::convert::fr_to_u256;
+use crate::utils::ct_select;
+
+const GENPOINT_PREFIX: &str = "PedersenGenerator";
+const WINDOW_SIZE: usize = 4;
+const N_WINDOWS_PER_SEGMENT: usize = 50;
+const BITS_PER_SEGMENT: usize = WINDOW_SIZE * N_WINDOWS_PER_SEGMENT;
+/// Segments of a 62 byte tornado preimage
+const TORNADO_SEGMENTS: usize = 3;
+
+pub struct Pedersen {
+    bases: Vec<Point>,
+}
+
+impl Default for Pedersen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pedersen {
+    /// Precomputes the generators needed for tornado notes.
+    pub fn new() -> Self {
+        Self { bases: (0..TORNADO_SEGMENTS).map(base_point).collect() }
+    }
+
+    pub fn hash_point(&self, msg: &[u8]) -> Point {
+        let bits: Vec<u8> = msg.iter().flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1)).collect();
+
+        let mut acc = Projective::identity();
+        for (s, segment) in bits.chunks(BITS_PER_SEGMENT).enumerate() {
+            // every window encodes (1 + b0 + 2 b1 + 4 b2) * (-1)^b3 * 32^w;
+            // the bits are secret, so no branches on them
+            let (mut pos, mut neg) = (U256::ZERO, U256::ZERO);
+            for (w, window) in segment.chunks(WINDOW_SIZE).enumerate() {
+                let mut v = U256::ONE;
+                for (b, bit) in window.iter().take(WINDOW_SIZE - 1).enumerate() {
+                    v += U256::from(*bit) << b;
+                }
+                v <<= (WINDOW_SIZE + 1) * w;
+                let negative = Choice::from(window.get(WINDOW_SIZE - 1).copied().unwrap_or(0));
+                neg += ct_select(negative, &v, &U256::ZERO);
+                pos += ct_select(negative, &U256::ZERO, &v);
+            }
+            let (diff, borrow) = pos.overflowing_sub(neg);
+            let scalar = ct_select(Choice::from(borrow as u8), &diff.wrapping_add(sub_order()), &diff);
+
+            let base = match self.bases.get(s) {
+                Some(base) => *base,
+                None => base_point(s),
+            };
+            acc = acc.add(&base.mul_projective_ct(&scalar));
+        }
+        acc.into()
+    }
+
+    /// circomlib `pedersenHash`: the packed point.
+    pub fn hash(&self, msg: &[u8]) -> [u8; 32] {
+        self.hash_point(msg).pack()
+    }
+
+    pub fn commitment(&self, nullifier: &[u8; 31], secret: &[u8; 31]) -> U256 {
+        let mut preimage = nullifier.to_vec();
+        preimage.extend_from_slice(secret);
+        fr_to_u256(&self.hash_point(&preimage).x)
+    }
+
+    pub fn nullifier_hash(&self, nullifier: &[u8; 31]) -> U256 {
+        fr_to_u256(&self.hash_point(nullifier).x)
+    }
+}
+
+/// Generator of segment `index`: the first blake256 hash of
+/// `PedersenGenerator_<index>_<try>` that unpacks to a point, times 8.
+pub fn base_point(index: usize) -> Point {
+    (0..)
+        .find_map(|try_index| {
+            let seed = format!("{}_{:0>32}_{:0>32}", GENPOINT_PREFIX, index, try_index);
+            let mut h = blake256(seed.as_bytes());
+            // keep the packed y below 2^254
+            h[31] &= 0xbf;
+            Point::unpack(&h)
+        })
+        .map(|p| p.mul_scalar(&U256::new(8)))
+        .expect("a point is found eventually")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bignum;
+    use crate::convert::fr_from_u256_mod;
+
+    #[test]
+    fn test_base_points() {
+        let p = Pedersen::new();
+        let base = p.bases[0];
+        assert_eq!(fr_to_u256(&base.x), *bignum!("10457101036533406547632367118273992217979173478358440826365724437999023779287"));
+        assert_eq!(fr_to_u256(&base.y), *bignum!("19824078218392094440610104313265183977899662750282163392862422243483260492317"));
+        assert!(base.in_subgroup());
+
+        for (s, base) in circomlib_bases().iter().enumerate() {
+            assert_eq!(base_point(s), *base, "{}", s);
+        }
+        assert_eq!(p.bases, circomlib_bases()[..TORNADO_SEGMENTS]);
+    }
+
+    /// `PBASE` of circomlib's pedersen tests
+    fn circomlib_bases() -> [Point; 4] {
+        let point = |x: &str, y: &str| Point {
+            x: fr_from_u256_mod(&crate::convert::parse_decimal(x).unwrap()),
+            y: fr_from_u256_mod(&crate::convert::parse_decimal(y).unwrap()),
+        };
+        [
+            point(
+                "10457101036533406547632367118273992217979173478358440826365724437999023779287",
+                "19824078218392094440610104313265183977899662750282163392862422243483260492317",
+            ),
+            point(
+                "2671756056509184035029146175565761955751135805354291559563293617232983272177",
+                "2663205510731142763556352975002641716101654201788071096152948830924149045094",
+            ),
+            point(
+                "5802099305472655231388284418920769829666717045250560929368476121199858275951",
+                "5980429700218124965372158798884772646841287887664001482443826541541529227896",
+            ),
+            point(
+                "7107336197374528537877327281242680114152313102022415488494307685842428166594",
+                "2857869773864086953506483169737724679646433914307247183624878062391496185654",
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_windows() {
+        let p = Pedersen::new();
+        let base = p.bases[0];
+        // 0b0001_0110: windows (1 + 2 + 4) * 1 and -(1 + 1)
+        let expected = base.mul_scalar(&U256::new(7)).add(&base.mul_scalar(&(sub_order() - U256::new(2 * 32))));
+        assert_eq!(p.hash_point(&[0b1001_0110]), expected);
+        assert_eq!(p.hash(&[0b1001_0110]), expected.pack());
+
+        // a second segment starts after 25 bytes, zero windows still count one
+        let mut msg = [0u8; 26];
+        msg[25] = 1;
+        let ones = (0..N_WINDOWS_PER_SEGMENT).fold(U256::ZERO, |acc, w| acc + (U256::ONE << (5 * w)));
+        let expected = base.mul_scalar(&ones).add(&p.bases[1].mul_scalar(&U256::new(2 + 32)));
+        assert_eq!(p.hash_point(&msg), expected);
+    }
+
+    #[test]
+    fn test_tornado_note() {
+        let p = Pedersen::new();
+        let nullifier = [1u8; 31];
+        let secret = [2u8; 31];
+        let commitment = p.commitment(&nullifier, &secret);
+        assert_ne!(commitment, p.nullifier_hash(&nullifier));
+        assert_eq!(p.nullifier_hash(&nullifier), fr_to_u256(&p.hash_point(&nullifier).x));
+        let packed = p.hash(&[nullifier, secret].concat());
+        assert_eq!(Point::unpack(&packed).unwrap().x, fr_from_u256_mod(&commitment));
+
+        // an all-zero note from circomlib's generators alone: every window
+        // of a zero segment is worth 32^w, 496 bits make two full segments
+        // and 24 windows, 248 bits one full segment and 12 windows
+        let bases = circomlib_bases();
+        let ones = |windows: usize| (0..windows).fold(U256::ZERO, |acc, w| acc + (U256::ONE << (5 * w)));
+        let full = ones(N_WINDOWS_PER_SEGMENT);
+        let expected = bases[0].mul_scalar(&full).add(&bases[1].mul_scalar(&full)).add(&bases[2].mul_scalar(&ones(24)));
+        assert_eq!(p.commitment(&[0; 31], &[0; 31]), fr_to_u256(&expected.x));
+        let expected = bases[0].mul_scalar(&full).add(&bases[1].mul_scalar(&ones(12)));
+        assert_eq!(p.nullifier_hash(&[0; 31]), fr_to_u256(&expected.x));
+    }
+}
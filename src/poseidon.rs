@@ -0,0 +1,193 @@
+//! https://github.com/iden3/circomlib/blob/master/src/poseidon.js
+//!
+//! Round constants and MDS matrices are generated like the reference
+//! `generate_parameters_grain.sage` (which produced circomlib's tables):
+//! a Grain LFSR seeded with the field, S-box, width and round numbers.
+use ark_bn254::{Fr, FrParameters};
+use ark_ff::{Field, FpParameters, Zero};
+use ethnum::U256;
+
+use crate::convert::{fr_from_u256_mod, fr_to_u256, limbs_to_u256};
+
+const N_ROUNDS_F: usize = 8;
+/// Partial rounds for t = 2..=6
+const N_ROUNDS_P: [usize; 5] = [56, 57, 56, 60, 60];
+/// Bits per field element
+const N_BITS: usize = 254;
+
+pub struct Poseidon {
+    t: usize,
+    n_rounds_p: usize,
+    /// `(N_ROUNDS_F + n_rounds_p) * t` constants, round by round
+    constants: Vec<Fr>,
+    mds: Vec<Vec<Fr>>,
+}
+
+impl Poseidon {
+    /// Permutation of width `t`, hashing `t - 1` inputs.
+    pub fn new(t: usize) -> Self {
+        assert!((2..=6).contains(&t), "width should be between 2 and 6");
+        let n_rounds_p = N_ROUNDS_P[t - 2];
+        let mut grain = Grain::new(t, N_ROUNDS_F, n_rounds_p);
+
+        let constants = (0..(N_ROUNDS_F + n_rounds_p) * t)
+            .map(|_| grain.field_element())
+            .collect();
+
+        // Cauchy matrix 1 / (x_i + y_j) over 2t distinct random elements
+        let mds = loop {
+            let xy: Vec<Fr> = (0..2 * t).map(|_| fr_from_u256_mod(&grain.next_u256())).collect();
+            let distinct = (0..2 * t).all(|i| !xy[..i].contains(&xy[i]));
+            let (xs, ys) = xy.split_at(t);
+            let mds: Option<Vec<Vec<Fr>>> = xs
+                .iter()
+                .map(|x| ys.iter().map(|y| (*x + y).inverse()).collect())
+                .collect();
+            match mds {
+                Some(mds) if distinct => break mds,
+                _ => continue,
+            }
+        };
+
+        Self { t, n_rounds_p, constants, mds }
+    }
+
+    pub fn width(&self) -> usize {
+        self.t
+    }
+
+    /// circomlib `Poseidon(t - 1)`: the permutation applied to `[0, inputs..]`,
+    /// returning the first element.
+    pub fn hash_fr(&self, inputs: &[Fr]) -> Fr {
+        assert_eq!(inputs.len(), self.t - 1, "expected t - 1 inputs");
+        let mut state = Vec::with_capacity(self.t);
+        state.push(Fr::zero());
+        state.extend_from_slice(inputs);
+
+        let half_f = N_ROUNDS_F / 2;
+        for r in 0..(N_ROUNDS_F + self.n_rounds_p) {
+            for (s, c) in state.iter_mut().zip(&self.constants[r * self.t..]) {
+                *s += c;
+            }
+            if r < half_f || r >= half_f + self.n_rounds_p {
+                state.iter_mut().for_each(pow5);
+            } else {
+                pow5(&mut state[0]);
+            }
+            state = self
+                .mds
+                .iter()
+                .map(|row| row.iter().zip(&state).map(|(m, s)| *m * s).sum())
+                .collect();
+        }
+        state[0]
+    }
+
+    pub fn hash(&self, inputs: &[U256]) -> U256 {
+        let inputs: Vec<Fr> = inputs.iter().map(fr_from_u256_mod).collect();
+        fr_to_u256(&self.hash_fr(&inputs))
+    }
+}
+
+fn pow5(x: &mut Fr) {
+    let x2 = x.square();
+    *x *= x2.square();
+}
+
+/// Self-shrinking Grain LFSR of the reference parameter generation script.
+struct Grain {
+    state: [bool; 80],
+}
+
+impl Grain {
+    fn new(t: usize, n_rounds_f: usize, n_rounds_p: usize) -> Self {
+        // field = 1 (prime), sbox = 0 (x^5), n, t, R_F, R_P, then 30 ones
+        let fields = [(1, 2), (0, 4), (N_BITS, 12), (t, 12), (n_rounds_f, 10), (n_rounds_p, 10)];
+        let mut state = [true; 80];
+        let mut i = 0;
+        for (value, bits) in fields {
+            for b in (0..bits).rev() {
+                state[i] = (value >> b) & 1 == 1;
+                i += 1;
+            }
+        }
+
+        let mut grain = Self { state };
+        for _ in 0..160 {
+            grain.update();
+        }
+        grain
+    }
+
+    fn update(&mut self) -> bool {
+        let s = &self.state;
+        let bit = s[62] ^ s[51] ^ s[38] ^ s[23] ^ s[13] ^ s[0];
+        self.state.copy_within(1.., 0);
+        self.state[79] = bit;
+        bit
+    }
+
+    /// Bits come in pairs, the second is output only if the first is set.
+    fn next_bit(&mut self) -> bool {
+        while !self.update() {
+            self.update();
+        }
+        self.update()
+    }
+
+    /// `N_BITS` bits, most significant first.
+    fn next_u256(&mut self) -> U256 {
+        (0..N_BITS).fold(U256::ZERO, |acc, _| (acc << 1) | U256::from(self.next_bit() as u8))
+    }
+
+    /// Uniform element by rejection sampling.
+    fn field_element(&mut self) -> Fr {
+        let modulus = limbs_to_u256(&FrParameters::MODULUS.0);
+        loop {
+            let x = self.next_u256();
+            if x < modulus {
+                return fr_from_u256_mod(&x);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bignum;
+
+    fn hash(inputs: &[u128]) -> U256 {
+        let inputs: Vec<U256> = inputs.iter().map(|x| U256::new(*x)).collect();
+        Poseidon::new(inputs.len() + 1).hash(&inputs)
+    }
+
+    #[test]
+    fn test_constants() {
+        let p = Poseidon::new(3);
+        assert_eq!(p.constants.len(), 65 * 3);
+        assert_eq!(
+            fr_to_u256(&p.constants[0]),
+            *bignum!("6745197990210204598374042828761989596302876299545964402857411729872131034734"),
+        );
+    }
+
+    #[test]
+    fn test_hash() {
+        assert_eq!(hash(&[1]), *bignum!("18586133768512220936620570745912940619677854269274689475585506675881198879027"));
+        assert_eq!(hash(&[1, 2]), *bignum!("7853200120776062878684798364095072458815029376092732009249414926327459813530"));
+        assert_eq!(hash(&[1, 2, 3]), *bignum!("6542985608222806190361240322586112750744169038454362455181422643027100751666"));
+        assert_eq!(hash(&[1, 2, 3, 4]), *bignum!("18821383157269793795438455681495246036402687001665670618754263018637548127333"));
+        assert_eq!(hash(&[1, 2, 3, 4, 5]), *bignum!("6183221330272524995739186171720101788151706631170188140075976616310159254464"));
+    }
+
+    #[test]
+    fn test_tree_hasher() {
+        use crate::merkle_tree::MerkleTreeWithHistory;
+        let p = Poseidon::new(3);
+        let mut mt = MerkleTreeWithHistory::with_hasher(1, 10, Poseidon::new(3));
+        mt.insert(&U256::new(1));
+        mt.insert(&U256::new(2));
+        assert_eq!(*mt.getLastRoot(), p.hash(&[U256::new(1), U256::new(2)]));
+    }
+}
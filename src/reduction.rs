@@ -0,0 +1,305 @@
+//! Modular multiplication on 4x64-bit limbs for any 256-bit modulus, as
+//! faster alternatives to the bit-serial `utils::mulmod`.
+use ethnum::U256;
+
+use crate::convert::{limbs_to_u256, u256_to_limbs};
+use crate::utils::mulmod;
+
+/// `a + b * c + carry`, as (low, high) words
+#[inline(always)]
+fn mac(a: u64, b: u64, c: u64, carry: u64) -> (u64, u64) {
+    let t = a as u128 + (b as u128) * (c as u128) + carry as u128;
+    (t as u64, (t >> 64) as u64)
+}
+
+/// Montgomery multiplication (CIOS) modulo an odd `m`. Values are kept as
+/// `a * 2^256 mod m`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Montgomery {
+    m: [u64; 4],
+    /// `-m^-1 mod 2^64`
+    inv: u64,
+    /// `2^512 mod m`, to enter the Montgomery domain
+    r2: [u64; 4],
+}
+
+impl Montgomery {
+    /// `None` unless `m` is odd and greater than one.
+    pub fn new(m: &U256) -> Option<Self> {
+        if *m & U256::ONE == U256::ZERO || *m == U256::ONE {
+            return None;
+        }
+        let limbs = u256_to_limbs(m);
+
+        let mut inv = 1u64;
+        for _ in 0..63 {
+            inv = inv.wrapping_mul(inv);
+            inv = inv.wrapping_mul(limbs[0]);
+        }
+
+        let r = (U256::MAX % m + 1) % m;
+        let r2 = u256_to_limbs(&mulmod(&r, &r, m));
+        Some(Self { m: limbs, inv: inv.wrapping_neg(), r2 })
+    }
+
+    pub fn modulus(&self) -> U256 {
+        limbs_to_u256(&self.m)
+    }
+
+    /// `a * b / 2^256 mod m` for `a, b < m`.
+    pub fn mont_mul(&self, a: &[u64; 4], b: &[u64; 4]) -> [u64; 4] {
+        let m = &self.m;
+        let mut t = [0u64; 6];
+        for bi in b.iter() {
+            let mut carry = 0;
+            for j in 0..4 {
+                let (lo, hi) = mac(t[j], a[j], *bi, carry);
+                t[j] = lo;
+                carry = hi;
+            }
+            let (sum, overflow) = t[4].overflowing_add(carry);
+            t[4] = sum;
+            t[5] = overflow as u64;
+
+            let u = t[0].wrapping_mul(self.inv);
+            let (_, mut carry) = mac(t[0], u, m[0], 0);
+            for j in 1..4 {
+                let (lo, hi) = mac(t[j], u, m[j], carry);
+                t[j - 1] = lo;
+                carry = hi;
+            }
+            let (sum, overflow) = t[4].overflowing_add(carry);
+            t[3] = sum;
+            t[4] = t[5] + overflow as u64;
+        }
+
+        let mut out = [t[0], t[1], t[2], t[3]];
+        if t[4] != 0 || limbs_to_u256(&out) >= limbs_to_u256(m) {
+            let mut borrow = false;
+            for j in 0..4 {
+                let (d, b1) = out[j].overflowing_sub(m[j]);
+                let (d, b2) = d.overflowing_sub(borrow as u64);
+                out[j] = d;
+                borrow = b1 || b2;
+            }
+        }
+        out
+    }
+
+    pub fn to_mont(&self, a: &U256) -> [u64; 4] {
+        let a = if limbs_to_u256(&self.m) <= *a { a % self.modulus() } else { *a };
+        self.mont_mul(&u256_to_limbs(&a), &self.r2)
+    }
+
+    pub fn from_mont(&self, a: &[u64; 4]) -> U256 {
+        limbs_to_u256(&self.mont_mul(a, &[1, 0, 0, 0]))
+    }
+
+    pub fn mulmod(&self, a: &U256, b: &U256) -> U256 {
+        self.from_mont(&self.mont_mul(&self.to_mont(a), &self.to_mont(b)))
+    }
+
+    pub fn pow(&self, base: &U256, exp: &U256) -> U256 {
+        let mut result = self.to_mont(&U256::ONE);
+        let base = self.to_mont(base);
+        for i in (0..(256 - exp.leading_zeros())).rev() {
+            result = self.mont_mul(&result, &result);
+            if (exp >> i) & U256::ONE == U256::ONE {
+                result = self.mont_mul(&result, &base);
+            }
+        }
+        self.from_mont(&result)
+    }
+}
+
+/// Barrett reduction (HAC 14.42) modulo any `m > 0` of `k` 64-bit limbs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Barrett {
+    m: [u64; 4],
+    k: usize,
+    /// `floor(2^(128 k) / m)`, `k + 1` limbs or `k + 2` if `m` is `2^(64 (k - 1))`
+    mu: [u64; 6],
+}
+
+impl Barrett {
+    pub fn new(m: &U256) -> Option<Self> {
+        if *m == U256::ZERO {
+            return None;
+        }
+        let limbs = u256_to_limbs(m);
+        let k = 4 - limbs.iter().rev().take_while(|l| **l == 0).count();
+
+        // long division of 2^(128 k) by m, one bit at a time
+        let mut mu = [0u64; 6];
+        let mut rem = [0u64; 5];
+        for bit in (0..=(128 * k)).rev() {
+            shl1(&mut rem);
+            if bit == 128 * k {
+                rem[0] |= 1;
+            }
+            if geq(&rem, &limbs) {
+                sub(&mut rem, &limbs);
+                mu[bit / 64] |= 1 << (bit % 64);
+            }
+        }
+        Some(Self { m: limbs, k, mu })
+    }
+
+    pub fn modulus(&self) -> U256 {
+        limbs_to_u256(&self.m)
+    }
+
+    pub fn mulmod(&self, a: &U256, b: &U256) -> U256 {
+        let m = self.modulus();
+        let a = if *a >= m { a % m } else { *a };
+        let b = if *b >= m { b % m } else { *b };
+        let k = self.k;
+
+        let mut x = [0u64; 8];
+        mul(&u256_to_limbs(&a), &u256_to_limbs(&b), &mut x);
+
+        // q3 = floor(floor(x / b^(k-1)) * mu / b^(k+1))
+        let mut q2 = [0u64; 11];
+        mul(&x[k - 1..], &self.mu[..k + 2], &mut q2);
+        let q3 = &q2[k + 1..];
+
+        // r = (x - q3 m) mod b^(k+1), then at most two subtractions
+        let mut qm = [0u64; 5];
+        mul(q3, &self.m[..k], &mut qm[..=k]);
+        let mut r = [0u64; 5];
+        r[..=k].copy_from_slice(&x[..=k]);
+        sub(&mut r[..=k], &qm[..=k]);
+        while geq(&r, &self.m) {
+            sub(&mut r, &self.m);
+        }
+        limbs_to_u256(&[r[0], r[1], r[2], r[3]])
+    }
+}
+
+/// `out = a * b`, truncated to `out.len()` limbs
+fn mul(a: &[u64], b: &[u64], out: &mut [u64]) {
+    out.iter_mut().for_each(|o| *o = 0);
+    for (i, ai) in a.iter().enumerate() {
+        let mut carry = 0;
+        for (j, bj) in b.iter().enumerate() {
+            if i + j >= out.len() {
+                break;
+            }
+            let (lo, hi) = mac(out[i + j], *ai, *bj, carry);
+            out[i + j] = lo;
+            carry = hi;
+        }
+        if i + b.len() < out.len() {
+            out[i + b.len()] = carry;
+        }
+    }
+}
+
+/// `a -= b` modulo `2^(64 a.len())`
+fn sub(a: &mut [u64], b: &[u64]) {
+    let mut borrow = false;
+    for (i, ai) in a.iter_mut().enumerate() {
+        let (d, b1) = ai.overflowing_sub(*b.get(i).unwrap_or(&0));
+        let (d, b2) = d.overflowing_sub(borrow as u64);
+        *ai = d;
+        borrow = b1 || b2;
+    }
+}
+
+/// `a >= b`, missing limbs count as zero
+fn geq(a: &[u64], b: &[u64]) -> bool {
+    for i in (0..a.len().max(b.len())).rev() {
+        let (x, y) = (*a.get(i).unwrap_or(&0), *b.get(i).unwrap_or(&0));
+        if x != y {
+            return x > y;
+        }
+    }
+    true
+}
+
+fn shl1(a: &mut [u64]) {
+    let mut carry = 0;
+    for limb in a.iter_mut() {
+        let next = *limb >> 63;
+        *limb = (*limb << 1) | carry;
+        carry = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bignum;
+
+    /// xorshift, deterministic so that failures reproduce
+    struct Rng(u64);
+
+    impl Rng {
+        fn next_u64(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        /// random width too, so small moduli and operands get covered
+        fn next_u256(&mut self) -> U256 {
+            let x = limbs_to_u256(&[self.next_u64(), self.next_u64(), self.next_u64(), self.next_u64()]);
+            x >> (self.next_u64() % 256) as u32
+        }
+    }
+
+    #[test]
+    fn test_differential() {
+        let mut rng = Rng(0x9e3779b97f4a7c15);
+        let fr = *bignum!("21888242871839275222246405745257275088548364400416034343698204186575808495617");
+        let mut moduli = vec![fr, U256::MAX, U256::new(3), U256::ONE << 255 | U256::ONE];
+        moduli.extend((0..60).map(|_| rng.next_u256() | U256::ONE));
+
+        for m in moduli {
+            if m == U256::ONE {
+                continue;
+            }
+            let mont = Montgomery::new(&m).unwrap();
+            let barrett = Barrett::new(&m).unwrap();
+            let even = Barrett::new(&(m - 1)).unwrap();
+            for _ in 0..20 {
+                let (a, b) = (rng.next_u256(), rng.next_u256());
+                let expected = mulmod(&a, &b, &m);
+                assert_eq!(mont.mulmod(&a, &b), expected, "montgomery {} * {} mod {}", a, b, m);
+                assert_eq!(barrett.mulmod(&a, &b), expected, "barrett {} * {} mod {}", a, b, m);
+                assert_eq!(even.mulmod(&a, &b), mulmod(&a, &b, &(m - 1)), "barrett {} * {} mod {}", a, b, m - 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_edge_cases() {
+        assert!(Montgomery::new(&U256::new(10)).is_none());
+        assert!(Montgomery::new(&U256::ONE).is_none());
+        assert!(Barrett::new(&U256::ZERO).is_none());
+
+        let m = U256::MAX;
+        let mont = Montgomery::new(&m).unwrap();
+        let barrett = Barrett::new(&m).unwrap();
+        let a = m - 1;
+        assert_eq!(mont.mulmod(&a, &a), U256::ONE);
+        assert_eq!(barrett.mulmod(&a, &a), U256::ONE);
+        assert_eq!(mont.mulmod(&m, &a), U256::ZERO);
+        assert_eq!(Barrett::new(&U256::ONE).unwrap().mulmod(&a, &a), U256::ZERO);
+        for shift in [64, 128, 192] {
+            let m = U256::ONE << shift;
+            assert_eq!(Barrett::new(&m).unwrap().mulmod(&a, &(a - 2)), mulmod(&a, &(a - 2), &m));
+        }
+    }
+
+    #[test]
+    fn test_pow() {
+        let m = bignum!("21888242871839275222246405745257275088548364400416034343698204186575808495617");
+        let b = bignum!("7120861356467848435263064379192047478074060781135320967663101236819528304087");
+        let expected = bignum!("10745147226606258107230518846003648962088186972186954460246329063381405757050");
+        let mont = Montgomery::new(&m).unwrap();
+        assert_eq!(mont.pow(&b, &U256::new(5)), *expected);
+        assert_eq!(mont.pow(&b, &U256::ZERO), U256::ONE);
+    }
+}
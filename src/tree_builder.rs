@@ -0,0 +1,159 @@
+//! Off-chain construction of a complete tree in one pass, hashing every level
+//! across threads when the `parallel` feature is enabled. Roots are identical
+//! to inserting the same leaves into a `MerkleTreeWithHistory`.
+use ark_bn254::Fr;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::convert::{fr_from_u256_mod, fr_to_u256};
+use crate::hasher::TreeHasher;
+use crate::merkle_tree::{zero_hashes, ZEROS};
+use crate::mimcsponge::MimcSponge;
+use crate::U256;
+
+pub struct FullTree {
+    pub levels: u32,
+    /// `layers[0]` are the leaves, `layers[levels]` holds only the root.
+    /// Nodes right of the last leaf are implicitly zero hashes.
+    pub layers: Vec<Vec<U256>>,
+    /// roots of empty subtrees, by height
+    zeros: Vec<U256>,
+}
+
+impl FullTree {
+    /// Hashes with the standard `MimcSponge`.
+    pub fn build(levels: u32, leaves: &[U256]) -> Option<Self> {
+        assert!(levels < 32, "_treeLevels should be less than 32");
+        Self::build_with_zeros(levels, leaves, &MimcSponge::new(), ZEROS[..=(levels as usize)].to_vec())
+    }
+
+    pub fn build_with_hasher<H: TreeHasher + Sync>(levels: u32, leaves: &[U256], hasher: &H) -> Option<Self> {
+        Self::build_with_zeros(levels, leaves, hasher, zero_hashes(hasher, levels))
+    }
+
+    fn build_with_zeros<H: TreeHasher + Sync>(levels: u32, leaves: &[U256], hasher: &H, zeros: Vec<U256>) -> Option<Self> {
+        if leaves.len() > 2_usize.saturating_pow(levels) {
+            return None;
+        }
+
+        let mut layers = vec![leaves.to_vec()];
+        for level in 0..(levels as usize) {
+            let parents = hash_layer(hasher, &layers[level], &zeros[level]);
+            layers.push(parents);
+        }
+        if layers[levels as usize].is_empty() {
+            layers[levels as usize].push(zeros[levels as usize]);
+        }
+
+        Some(Self { levels, layers, zeros })
+    }
+
+    pub fn root(&self) -> U256 {
+        self.layers[self.levels as usize][0]
+    }
+
+    /// Siblings of leaf `index` from the leaf level upwards.
+    pub fn path(&self, index: usize) -> Option<Vec<U256>> {
+        if index >= self.layers[0].len() {
+            return None;
+        }
+        let path = (0..(self.levels as usize))
+            .map(|level| {
+                let sibling = (index >> level) ^ 1;
+                self.layers[level].get(sibling).copied().unwrap_or(self.zeros[level])
+            })
+            .collect();
+        Some(path)
+    }
+}
+
+/// Pairs up a level, padding an odd last node with `zero`.
+fn pairs(layer: &[U256], zero: &U256) -> Vec<(Fr, Fr)> {
+    layer
+        .chunks(2)
+        .map(|pair| (fr_from_u256_mod(&pair[0]), fr_from_u256_mod(pair.get(1).unwrap_or(zero))))
+        .collect()
+}
+
+/// Pairs hashed per task, enough to fill the batched permutation lanes
+#[cfg(feature = "parallel")]
+const CHUNK_PAIRS: usize = 64;
+
+#[cfg(feature = "parallel")]
+fn hash_layer<H: TreeHasher + Sync>(hasher: &H, layer: &[U256], zero: &U256) -> Vec<U256> {
+    pairs(layer, zero)
+        .par_chunks(CHUNK_PAIRS)
+        .flat_map_iter(|chunk| hasher.hash_pairs(chunk).into_iter().map(|node| fr_to_u256(&node)))
+        .collect()
+}
+
+#[cfg(not(feature = "parallel"))]
+fn hash_layer<H: TreeHasher>(hasher: &H, layer: &[U256], zero: &U256) -> Vec<U256> {
+    hasher.hash_pairs(&pairs(layer, zero)).iter().map(fr_to_u256).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::MerkleTreeWithHistory;
+    use crate::poseidon::Poseidon;
+
+    #[test]
+    fn test_matches_insert() {
+        let leaves: Vec<U256> = (1..6).map(U256::new).collect();
+        let mut mt = MerkleTreeWithHistory::new(3);
+        for leaf in &leaves {
+            mt.insert(leaf);
+        }
+
+        let tree = FullTree::build(3, &leaves).unwrap();
+        assert_eq!(tree.root(), *mt.getLastRoot());
+
+        let path = tree.path(4).unwrap();
+        assert_eq!(path[0], *mt.zeros[0]);
+        assert_eq!(path[2], tree.layers[2][0]);
+        assert!(tree.path(5).is_none());
+    }
+
+    #[test]
+    fn test_empty_and_full() {
+        let mt = MerkleTreeWithHistory::new(1);
+        assert_eq!(FullTree::build(1, &[]).unwrap().root(), *mt.getLastRoot());
+        assert!(FullTree::build(1, &[U256::new(1), U256::new(2), U256::new(3)]).is_none());
+    }
+
+    #[test]
+    fn test_chunk_boundaries() {
+        // 150 leaf pairs are three `CHUNK_PAIRS` tasks with `parallel`
+        let leaves: Vec<U256> = (1..301).map(U256::new).collect();
+        let mut mt = MerkleTreeWithHistory::new(9);
+        mt.insert_batch(&leaves).unwrap();
+
+        let tree = FullTree::build(9, &leaves).unwrap();
+        assert_eq!(tree.root(), *mt.getLastRoot());
+        for index in [0, 127, 128, 129, 255, 256, 299] {
+            let path = tree.path(index).unwrap();
+            let mut node = leaves[index];
+            for (level, sibling) in path.iter().enumerate() {
+                node = if (index >> level) & 1 == 1 {
+                    *mt.hashLeftRight(sibling, &node)
+                } else {
+                    *mt.hashLeftRight(&node, sibling)
+                };
+            }
+            assert_eq!(node, tree.root(), "{}", index);
+        }
+    }
+
+    #[test]
+    fn test_other_hasher() {
+        let leaves: Vec<U256> = (1..4).map(U256::new).collect();
+        let mut mt = MerkleTreeWithHistory::with_hasher(2, 1, Poseidon::new(3));
+        for leaf in &leaves {
+            mt.insert(leaf);
+        }
+        let tree = FullTree::build_with_hasher(2, &leaves, &Poseidon::new(3)).unwrap();
+        assert_eq!(tree.root(), *mt.getLastRoot());
+        assert_eq!(tree.path(2).unwrap()[0], *mt.zeros[0]);
+    }
+}
@@ -0,0 +1,301 @@
+//! Fixed-size byte layout of a `MerkleTreeWithHistory`, suitable for storing
+//! the tree in a Solana account. All integers are little-endian.
+//!
+//! | offset             | size           | field            |
+//! |--------------------|----------------|------------------|
+//! | 0                  | 4              | levels           |
+//! | 4                  | 4              | root history len |
+//! | 8                  | 8              | nextIndex        |
+//! | 16                 | 4              | currentRootIndex |
+//! | 20                 | 4              | reserved         |
+//! | 24                 | 32 * levels    | filledSubtrees   |
+//! | 24 + 32 * levels   | 32 * history   | roots            |
+//! | after roots        | 8 * history    | root leaf counts |
+//!
+//! The zero hashes and the sponge constants are not part of the state; both
+//! are constant tables, so neither `load` nor `TreeStateMut::insert` hashes
+//! anything besides the inserted path. `TreeState` reads the bytes in place,
+//! `TreeStateMut` also inserts in place, without building a tree.
+use crate::convert::{fr_from_u256_mod, fr_to_u256};
+use crate::hasher::TreeHasher;
+use crate::merkle_tree::{MerkleTreeWithHistory, ZEROS};
+use crate::mimcsponge::MimcSponge;
+use crate::U256;
+
+pub const HEADER_SIZE: usize = 24;
+const NODE_SIZE: usize = 32;
+const COUNT_SIZE: usize = 8;
+
+/// Number of bytes needed to store a tree of the given depth and history.
+pub fn account_size(levels: u32, root_history_size: usize) -> usize {
+    HEADER_SIZE + NODE_SIZE * (levels as usize + root_history_size) + COUNT_SIZE * root_history_size
+}
+
+fn counts_offset(levels: u32, root_history_size: usize) -> usize {
+    HEADER_SIZE + NODE_SIZE * (levels as usize + root_history_size)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+fn read_node(data: &[u8], offset: usize) -> U256 {
+    let mut bytes = [0u8; NODE_SIZE];
+    bytes.copy_from_slice(&data[offset..offset + NODE_SIZE]);
+    U256::from_le_bytes(bytes)
+}
+
+fn filled_subtree_offset(level: usize) -> usize {
+    HEADER_SIZE + NODE_SIZE * level
+}
+
+fn root_offset(levels: u32, slot: usize) -> usize {
+    HEADER_SIZE + NODE_SIZE * (levels as usize + slot)
+}
+
+/// Read-only view over a serialized tree. Nothing is copied until a field is
+/// accessed, so a withdraw can check a root without materializing the tree.
+pub struct TreeState<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> TreeState<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self, &'static str> {
+        if data.len() < HEADER_SIZE {
+            return Err("tree state is too small");
+        }
+        let this = Self { data };
+        let levels = this.levels();
+        if levels == 0 || levels >= 32 {
+            return Err("invalid tree levels");
+        }
+        if this.root_history_size() == 0 {
+            return Err("invalid root history size");
+        }
+        if data.len() < account_size(levels, this.root_history_size()) {
+            return Err("tree state is too small");
+        }
+        if this.current_root_index() >= this.root_history_size() {
+            return Err("invalid current root index");
+        }
+        if this.next_index() > 2_usize.saturating_pow(levels) {
+            return Err("invalid next index");
+        }
+        Ok(this)
+    }
+
+    pub fn levels(&self) -> u32 {
+        read_u32(self.data, 0)
+    }
+
+    pub fn root_history_size(&self) -> usize {
+        read_u32(self.data, 4) as usize
+    }
+
+    pub fn next_index(&self) -> usize {
+        read_u64(self.data, 8) as usize
+    }
+
+    pub fn current_root_index(&self) -> usize {
+        read_u32(self.data, 16) as usize
+    }
+
+    pub fn filled_subtree(&self, level: usize) -> U256 {
+        assert!(level < self.levels() as usize);
+        read_node(self.data, filled_subtree_offset(level))
+    }
+
+    pub fn root(&self, slot: usize) -> U256 {
+        assert!(slot < self.root_history_size());
+        read_node(self.data, root_offset(self.levels(), slot))
+    }
+
+    /// Number of leaves the tree held when the root in `slot` was computed.
+    pub fn root_leaf_count(&self, slot: usize) -> usize {
+        assert!(slot < self.root_history_size());
+        let offset = counts_offset(self.levels(), self.root_history_size()) + COUNT_SIZE * slot;
+        read_u64(self.data, offset) as usize
+    }
+
+    pub fn last_root(&self) -> U256 {
+        self.root(self.current_root_index())
+    }
+
+    pub fn is_known_root(&self, root: &U256) -> bool {
+        *root != U256::ZERO && (0..self.root_history_size()).any(|i| self.root(i) == *root)
+    }
+}
+
+/// Writable view over a serialized tree, for deposits that update the account
+/// data directly.
+pub struct TreeStateMut<'a> {
+    data: &'a mut [u8],
+}
+
+impl<'a> TreeStateMut<'a> {
+    pub fn new(data: &'a mut [u8]) -> Result<Self, &'static str> {
+        TreeState::new(data)?;
+        Ok(Self { data })
+    }
+
+    pub fn state(&self) -> TreeState<'_> {
+        TreeState { data: self.data }
+    }
+
+    /// tornado's `_insert` on the bytes, hashing with the standard
+    /// `MimcSponge`: one hash per level and nothing else. Returns the index
+    /// of the leaf, `None` if the tree is full.
+    pub fn insert(&mut self, leaf: &U256) -> Option<usize> {
+        let state = self.state();
+        let (levels, history) = (state.levels(), state.root_history_size());
+        let next_index = state.next_index();
+        if next_index == 2_usize.saturating_pow(levels) {
+            return None;
+        }
+
+        let hasher = MimcSponge::new();
+        let mut node = fr_from_u256_mod(leaf);
+        let mut index = next_index;
+        for (level, zero) in ZEROS.iter().enumerate().take(levels as usize) {
+            node = if index & 1 == 0 {
+                self.write_node(filled_subtree_offset(level), &fr_to_u256(&node));
+                hasher.hash_left_right(node, fr_from_u256_mod(zero))
+            } else {
+                let left = fr_from_u256_mod(&self.state().filled_subtree(level));
+                hasher.hash_left_right(left, node)
+            };
+            index /= 2;
+        }
+
+        let slot = (self.state().current_root_index() + 1) % history;
+        self.write_node(root_offset(levels, slot), &fr_to_u256(&node));
+        let count = counts_offset(levels, history) + COUNT_SIZE * slot;
+        self.data[count..count + COUNT_SIZE].copy_from_slice(&(next_index as u64 + 1).to_le_bytes());
+        self.data[8..16].copy_from_slice(&(next_index as u64 + 1).to_le_bytes());
+        self.data[16..20].copy_from_slice(&(slot as u32).to_le_bytes());
+        Some(next_index)
+    }
+
+    fn write_node(&mut self, offset: usize, node: &U256) {
+        self.data[offset..offset + NODE_SIZE].copy_from_slice(&node.to_le_bytes());
+    }
+}
+
+/// Rebuilds a tree from its serialized state.
+pub fn load(data: &[u8]) -> Result<MerkleTreeWithHistory, &'static str> {
+    let state = TreeState::new(data)?;
+    let mut mt = MerkleTreeWithHistory::with_history(state.levels(), state.root_history_size());
+    for i in 0..(state.levels() as usize) {
+        *mt.filledSubtrees[i] = state.filled_subtree(i);
+    }
+    for i in 0..state.root_history_size() {
+        *mt.roots[i] = state.root(i);
+        mt.root_leaf_counts[i] = state.root_leaf_count(i);
+    }
+    mt.nextIndex = state.next_index();
+    mt.currentRootIndex = state.current_root_index();
+    Ok(mt)
+}
+
+/// Serializes `mt` into `data`, which must be at least `account_size` bytes.
+pub fn store(mt: &MerkleTreeWithHistory, data: &mut [u8]) -> Result<(), &'static str> {
+    if data.len() < account_size(mt.levels, mt.roots.len()) {
+        return Err("tree state is too small");
+    }
+
+    data[0..4].copy_from_slice(&mt.levels.to_le_bytes());
+    data[4..8].copy_from_slice(&(mt.roots.len() as u32).to_le_bytes());
+    data[8..16].copy_from_slice(&(mt.nextIndex as u64).to_le_bytes());
+    data[16..20].copy_from_slice(&(mt.currentRootIndex as u32).to_le_bytes());
+    data[20..24].copy_from_slice(&[0u8; 4]);
+
+    let nodes = mt.filledSubtrees.iter().chain(mt.roots.iter());
+    for (i, node) in nodes.enumerate() {
+        let offset = HEADER_SIZE + NODE_SIZE * i;
+        data[offset..offset + NODE_SIZE].copy_from_slice(&node.to_le_bytes());
+    }
+
+    let counts = counts_offset(mt.levels, mt.roots.len());
+    for (i, count) in mt.root_leaf_counts.iter().enumerate() {
+        let offset = counts + COUNT_SIZE * i;
+        data[offset..offset + COUNT_SIZE].copy_from_slice(&(*count as u64).to_le_bytes());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_size() {
+        assert_eq!(account_size(20, 100), 24 + 32 * 120 + 8 * 100);
+    }
+
+    #[test]
+    fn test_store_load_roundtrip() {
+        let mut mt = MerkleTreeWithHistory::with_history(3, 4);
+        for i in 1..6 {
+            mt.insert(&U256::new(i));
+        }
+
+        let mut data = vec![0u8; account_size(3, 4)];
+        store(&mt, &mut data).unwrap();
+
+        let state = TreeState::new(&data).unwrap();
+        assert_eq!(state.levels(), 3);
+        assert_eq!(state.next_index(), 5);
+        assert_eq!(state.current_root_index(), 1);
+        assert_eq!(state.last_root(), *mt.getLastRoot());
+        assert!(state.is_known_root(&mt.roots[3]));
+        assert!(!state.is_known_root(&U256::ZERO));
+
+        let mut loaded = load(&data).unwrap();
+        assert_eq!(loaded.filledSubtrees, mt.filledSubtrees);
+        assert_eq!(loaded.roots, mt.roots);
+        assert_eq!(loaded.root_after(4), mt.root_after(4));
+        assert_eq!(loaded.roots_with_counts(), mt.roots_with_counts());
+
+        loaded.insert(&U256::new(6));
+        mt.insert(&U256::new(6));
+        assert_eq!(loaded.getLastRoot(), mt.getLastRoot());
+    }
+
+    #[test]
+    fn test_insert_in_place() {
+        let mut mt = MerkleTreeWithHistory::with_history(2, 3);
+        let mut data = vec![0u8; account_size(2, 3)];
+        store(&mt, &mut data).unwrap();
+
+        let mut expected = vec![0u8; data.len()];
+        for i in 0..4 {
+            let mut state = TreeStateMut::new(&mut data).unwrap();
+            assert_eq!(state.insert(&U256::new(i + 1)), Some(i as usize));
+            mt.insert(&U256::new(i + 1));
+            store(&mt, &mut expected).unwrap();
+            assert_eq!(data, expected, "{}", i);
+        }
+        assert_eq!(TreeStateMut::new(&mut data).unwrap().insert(&U256::new(5)), None);
+        assert_eq!(data, expected);
+    }
+
+    #[test]
+    fn test_invalid_state() {
+        let mt = MerkleTreeWithHistory::with_history(2, 2);
+        let mut data = vec![0u8; account_size(2, 2)];
+        assert!(store(&mt, &mut data[..10]).is_err());
+        store(&mt, &mut data).unwrap();
+        assert!(TreeState::new(&data[..data.len() - 1]).is_err());
+
+        data[16] = 2;
+        assert!(load(&data).is_err());
+    }
+}
@@ -1,5 +1,77 @@
+use ark_bn254::Fr;
+use ark_ff::PrimeField;
 use ethnum::U256;
+use subtle::{Choice, ConditionallySelectable};
 use crate::bignum;
+use crate::convert::{fr_to_u256, limbs_to_u256, u256_to_limbs};
+
+pub fn keccak256(bytes: &[u8]) -> [u8; 32] {
+    use tiny_keccak::{Hasher, Keccak};
+    let mut keccak = Keccak::v256();
+    let mut out = [0u8; 32];
+    keccak.update(bytes);
+    keccak.finalize(&mut out);
+    out
+}
+
+/// circomlib's round constant derivation: `c = keccak256(seed)`, then
+/// `c = keccak256(c)` for every round, each reduced modulo the BN254 scalar
+/// field. The first constant is zero.
+pub fn keccak_round_constants(seed: &str, n_rounds: usize) -> Vec<U256> {
+    let mut constants = vec![U256::ZERO; n_rounds];
+    let mut c = keccak256(seed.as_bytes());
+    for constant in constants.iter_mut().skip(1) {
+        c = keccak256(&c);
+        *constant = fr_to_u256(&Fr::from_be_bytes_mod_order(&c));
+    }
+    constants
+}
+
+pub fn sha256(bytes: &[u8]) -> [u8; 32] {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(bytes).into()
+}
+
+/// How `hash_to_field` turns bytes into a field element.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldHash {
+    /// keccak256 modulo p, as Solidity's `uint256(keccak256(..)) % p`
+    Keccak256,
+    /// sha256 modulo p
+    Sha256,
+    /// two keccak256 blocks (counter byte 0 and 1 appended) read as a 512 bit
+    /// integer modulo p, whose bias is negligible unlike the 256 bit variants
+    WideKeccak256,
+}
+
+/// Hashes `bytes` separated by `domain` (at most 255 bytes) into a canonical
+/// field element. The domain is prefixed with its length so that no two
+/// `(domain, bytes)` pairs share a preimage.
+pub fn hash_to_fr(method: FieldHash, domain: &[u8], bytes: &[u8]) -> Fr {
+    assert!(domain.len() <= 255, "domain should be at most 255 bytes");
+    let mut preimage = Vec::with_capacity(1 + domain.len() + bytes.len() + 1);
+    preimage.push(domain.len() as u8);
+    preimage.extend_from_slice(domain);
+    preimage.extend_from_slice(bytes);
+
+    match method {
+        FieldHash::Keccak256 => Fr::from_be_bytes_mod_order(&keccak256(&preimage)),
+        FieldHash::Sha256 => Fr::from_be_bytes_mod_order(&sha256(&preimage)),
+        FieldHash::WideKeccak256 => {
+            let mut wide = [0u8; 64];
+            for (i, half) in wide.chunks_mut(32).enumerate() {
+                preimage.push(i as u8);
+                half.copy_from_slice(&keccak256(&preimage));
+                preimage.pop();
+            }
+            Fr::from_be_bytes_mod_order(&wide)
+        }
+    }
+}
+
+pub fn hash_to_field(method: FieldHash, domain: &[u8], bytes: &[u8]) -> U256 {
+    fr_to_u256(&hash_to_fr(method, domain, bytes))
+}
 
 pub fn addmod(x: &U256, y: &U256, m: &U256) -> U256 {
     let (out, carry) = x.overflowing_add(*y);
@@ -23,6 +95,9 @@ pub fn mod_pow(base: &U256, exp: &U256, modulus: &U256) -> U256 {
     if modulus == &1 {
         return U256::new(0);
     }
+    if let Some(mont) = crate::reduction::Montgomery::new(modulus) {
+        return mont.pow(base, exp);
+    }
     let mut result = U256::new(1);
     let mut base = *base;
     let mut exp = *exp;
@@ -37,6 +112,164 @@ pub fn mod_pow(base: &U256, exp: &U256, modulus: &U256) -> U256 {
     result
 }
 
+/// `a` if `choice` is set else `b`, selected limb by limb.
+pub fn ct_select(choice: Choice, a: &U256, b: &U256) -> U256 {
+    let (a, b) = (u256_to_limbs(a), u256_to_limbs(b));
+    let mut limbs = [0u64; 4];
+    for (i, limb) in limbs.iter_mut().enumerate() {
+        *limb = u64::conditional_select(&b[i], &a[i], choice);
+    }
+    limbs_to_u256(&limbs)
+}
+
+/// Bit `i` of `a`, as a `Choice`.
+pub(crate) fn ct_bit(a: &U256, i: u32) -> Choice {
+    Choice::from((a >> i).as_u8() & 1)
+}
+
+/// `x + y mod m` for `x, y < m`, without branching on the values.
+fn ct_addmod(x: &U256, y: &U256, m: &U256) -> U256 {
+    let (sum, carry) = x.overflowing_add(*y);
+    let (reduced, borrow) = sum.overflowing_sub(*m);
+    ct_select(Choice::from((carry | !borrow) as u8), &reduced, &sum)
+}
+
+/// `a mod m` as a double-and-add over all 256 bits of `a`, so that a secret
+/// `a` is reduced without a division. The modulus is public.
+fn ct_reduce(a: &U256, m: &U256) -> U256 {
+    let one = U256::ONE % m;
+    let mut res = U256::ZERO;
+    for i in (0..256).rev() {
+        res = ct_addmod(&res, &res, m);
+        res = ct_addmod(&res, &ct_select(ct_bit(a, i), &one, &U256::ZERO), m);
+    }
+    res
+}
+
+/// `mulmod` that always runs through all 256 bits of `a` and only selects
+/// with masks, for secret operands (note nullifiers and secrets). Slower.
+/// `a` may be any value, `b` is reduced with `ct_reduce` first; only the
+/// modulus is public.
+pub fn ct_mulmod(a: &U256, b: &U256, m: &U256) -> U256 {
+    ct_mulmod_reduced(a, &ct_reduce(b, m), m)
+}
+
+/// `ct_mulmod` for `b < m`.
+fn ct_mulmod_reduced(a: &U256, b: &U256, m: &U256) -> U256 {
+    let mut res = U256::ZERO;
+    for i in (0..256).rev() {
+        res = ct_addmod(&res, &res, m);
+        res = ct_addmod(&res, &ct_select(ct_bit(a, i), b, &U256::ZERO), m);
+    }
+    res
+}
+
+/// `mod_pow` with a square and a multiply for every one of the 256 exponent
+/// bits, built on `ct_mulmod`.
+pub fn ct_pow(base: &U256, exp: &U256, m: &U256) -> U256 {
+    let base = ct_reduce(base, m);
+    let mut res = U256::ONE % m;
+    for i in (0..256).rev() {
+        res = ct_mulmod_reduced(&res, &res, m);
+        let product = ct_mulmod_reduced(&res, &base, m);
+        res = ct_select(ct_bit(exp, i), &product, &res);
+    }
+    res
+}
+
+/// `(x + y) / 2` without overflowing
+fn half_sum(x: &U256, y: &U256) -> U256 {
+    (x >> 1) + (y >> 1) + (x & y & U256::ONE)
+}
+
+/// Inverse of `a` modulo an odd `m` by binary extended GCD, `None` if `a`
+/// and `m` are not coprime.
+pub fn mod_inverse(a: &U256, m: &U256) -> Option<U256> {
+    assert!(m & U256::ONE == U256::ONE, "modulus should be odd");
+    let mut u = a % m;
+    let mut v = *m;
+    let (mut x1, mut x2) = (U256::ONE, U256::ZERO);
+    let halve = |x: &U256| if x & U256::ONE == U256::ZERO { x >> 1 } else { half_sum(x, m) };
+    let sub = |x: &U256, y: &U256| if x >= y { x - y } else { m - (y - x) };
+
+    // invariant: x1 a = u and x2 a = v (mod m)
+    while u != U256::ONE && v != U256::ONE {
+        if u == U256::ZERO || v == U256::ZERO {
+            return None;
+        }
+        while u & U256::ONE == U256::ZERO {
+            u >>= 1;
+            x1 = halve(&x1);
+        }
+        while v & U256::ONE == U256::ZERO {
+            v >>= 1;
+            x2 = halve(&x2);
+        }
+        if u >= v {
+            u -= v;
+            x1 = sub(&x1, &x2);
+        } else {
+            v -= u;
+            x2 = sub(&x2, &x1);
+        }
+    }
+    if *m == U256::ONE {
+        return Some(U256::ZERO);
+    }
+    Some(if u == U256::ONE { x1 } else { x2 } % m)
+}
+
+/// Legendre symbol of `a` modulo an odd prime `p`: 0, 1 or -1.
+pub fn legendre(a: &U256, p: &U256) -> i8 {
+    let e = mod_pow(a, &((p - 1) >> 1), p);
+    if e == U256::ZERO {
+        0
+    } else if e == U256::ONE {
+        1
+    } else {
+        -1
+    }
+}
+
+/// Square root of `a` modulo an odd prime `p` by Tonelli-Shanks, the smaller
+/// of the two roots, `None` for non-residues.
+pub fn mod_sqrt(a: &U256, p: &U256) -> Option<U256> {
+    let a = a % p;
+    match legendre(&a, p) {
+        0 => return Some(U256::ZERO),
+        -1 => return None,
+        _ => {}
+    }
+
+    // p - 1 = q 2^s with q odd
+    let s = (p - 1).trailing_zeros();
+    let q = (p - 1) >> s;
+    let mut z = U256::new(2);
+    while legendre(&z, p) != -1 {
+        z += 1;
+    }
+
+    let mut m = s;
+    let mut c = mod_pow(&z, &q, p);
+    let mut t = mod_pow(&a, &q, p);
+    let mut r = mod_pow(&a, &((q + 1) >> 1), p);
+    while t != U256::ONE {
+        // least i with t^(2^i) = 1
+        let mut i = 0;
+        let mut t2i = t;
+        while t2i != U256::ONE {
+            t2i = mulmod(&t2i, &t2i, p);
+            i += 1;
+        }
+        let b = mod_pow(&c, &(U256::ONE << (m - i - 1)), p);
+        m = i;
+        c = mulmod(&b, &b, p);
+        t = mulmod(&t, &c, p);
+        r = mulmod(&r, &b, p);
+    }
+    Some(r.min(p - r))
+}
+
 pub fn mulmod(a: &U256, b: &U256, m: &U256) -> U256 {
     let mut a = *a;
     let mut b = *b;
@@ -81,6 +314,8 @@ pub fn mulmod(a: &U256, b: &U256, m: &U256) -> U256 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ark_bn254::FrParameters;
+    use ark_ff::FpParameters;
 
     #[test]
     fn test_addmod_under_10() {
@@ -106,6 +341,106 @@ mod tests {
         assert_eq!(U256::new(1), addmod(&a, &b, &m));
     }
 
+    #[test]
+    fn test_keccak256() {
+        assert_eq!(
+            U256::from_be_bytes(keccak256(b"")),
+            U256::from_str_radix("c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470", 16).unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(
+            U256::from_be_bytes(sha256(b"abc")),
+            U256::from_str_radix("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad", 16).unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_hash_to_field() {
+        let p = limbs_to_u256(&FrParameters::MODULUS.0);
+        for method in [FieldHash::Keccak256, FieldHash::Sha256, FieldHash::WideKeccak256] {
+            let h = hash_to_field(method, b"ordinal", b"memo");
+            assert!(h < p);
+            assert_eq!(h, fr_to_u256(&hash_to_fr(method, b"ordinal", b"memo")));
+            assert_ne!(h, hash_to_field(method, b"ordina", b"lmemo"));
+            assert_ne!(h, hash_to_field(method, b"ordinal", b"memo2"));
+        }
+
+        let mut preimage = vec![7u8];
+        preimage.extend_from_slice(b"ordinalmemo");
+        let digest = U256::from_be_bytes(keccak256(&preimage));
+        assert_eq!(hash_to_field(FieldHash::Keccak256, b"ordinal", b"memo"), digest % p);
+        assert_ne!(hash_to_field(FieldHash::Keccak256, b"ordinal", b"memo"), hash_to_field(FieldHash::WideKeccak256, b"ordinal", b"memo"));
+
+        // 2^256 hi + lo, reduced with the field's own arithmetic
+        preimage.push(0);
+        let hi = keccak256(&preimage);
+        *preimage.last_mut().unwrap() = 1;
+        let lo = keccak256(&preimage);
+        let r = Fr::from_be_bytes_mod_order(&[[1u8].as_ref(), &[0u8; 32]].concat());
+        let expected = Fr::from_be_bytes_mod_order(&hi) * r + Fr::from_be_bytes_mod_order(&lo);
+        assert_eq!(hash_to_fr(FieldHash::WideKeccak256, b"ordinal", b"memo"), expected);
+    }
+
+    #[test]
+    fn test_inverse_sqrt_against_arkworks() {
+        use ark_bn254::{Fq, FqParameters};
+        use ark_ff::SquareRootField;
+
+        fn check<F: PrimeField + SquareRootField>(p: U256, to_u256: impl Fn(&F) -> U256) {
+            for x in 0u64..40 {
+                let x = x.wrapping_mul(0x9e3779b97f4a7c15) + 1;
+                let f = F::from(x) * F::from(x).square();
+                let a = to_u256(&f);
+
+                assert_eq!(mod_inverse(&a, &p), f.inverse().map(|i| to_u256(&i)));
+                let ark = f.sqrt().map(|r| to_u256(&r));
+                let root = mod_sqrt(&a, &p);
+                assert_eq!(root, ark.map(|r| r.min(p - r)));
+                assert_eq!(legendre(&a, &p), if root.is_some() { 1 } else { -1 });
+            }
+        }
+        check::<Fr>(limbs_to_u256(&FrParameters::MODULUS.0), fr_to_u256);
+        check::<Fq>(limbs_to_u256(&FqParameters::MODULUS.0), |f: &Fq| limbs_to_u256(&f.into_repr().0));
+    }
+
+    #[test]
+    fn test_inverse_edge_cases() {
+        let p = limbs_to_u256(&FrParameters::MODULUS.0);
+        assert_eq!(mod_inverse(&U256::ZERO, &p), None);
+        assert_eq!(mod_inverse(&p, &p), None);
+        assert_eq!(mod_inverse(&U256::ONE, &p), Some(U256::ONE));
+        assert_eq!(mod_inverse(&(p - 1), &p), Some(p - 1));
+        assert_eq!(mod_inverse(&U256::new(6), &U256::new(9)), None);
+        assert_eq!(mod_inverse(&U256::new(4), &U256::new(9)), Some(U256::new(7)));
+        assert_eq!(mod_inverse(&(U256::MAX - 1), &U256::MAX), Some(U256::MAX - 1));
+        assert_eq!(mod_sqrt(&U256::ZERO, &p), Some(U256::ZERO));
+        assert_eq!(mod_sqrt(&U256::new(4), &p), Some(U256::new(2)));
+        assert_eq!(legendre(&p, &p), 0);
+    }
+
+    #[test]
+    fn test_constant_time() {
+        let p = limbs_to_u256(&FrParameters::MODULUS.0);
+        assert_eq!(ct_select(Choice::from(1), &U256::ONE, &U256::MAX), U256::ONE);
+        assert_eq!(ct_select(Choice::from(0), &U256::ONE, &U256::MAX), U256::MAX);
+        assert_eq!(ct_reduce(&U256::MAX, &p), U256::MAX % p);
+
+        let mut x = U256::new(0x9e3779b97f4a7c15);
+        for m in [p, U256::MAX, U256::new(97), U256::ONE << 255] {
+            for _ in 0..8 {
+                x = mulmod(&x, &x, &U256::MAX) ^ U256::new(0x243f6a8885a308d3);
+                let y = x.rotate_left(77);
+                assert_eq!(ct_mulmod(&x, &y, &m), mulmod(&x, &y, &m));
+                assert_eq!(ct_pow(&x, &y, &m), mod_pow(&x, &y, &m));
+            }
+        }
+        assert_eq!(ct_pow(&U256::new(5), &U256::ZERO, &p), U256::ONE);
+        assert_eq!(ct_pow(&U256::new(5), &U256::ZERO, &U256::ONE), U256::ZERO);
+    }
+
     #[test]
     fn test_pow_mod() {
         let b = bignum!("7120861356467848435263064379192047478074060781135320967663101236819528304087");
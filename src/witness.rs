@@ -0,0 +1,184 @@
+//! Incremental witnesses, in the spirit of Zcash's: a wallet keeps the tree
+//! frontier instead of the full tree and updates the authentication paths of
+//! its own leaves as later deposits are appended.
+use crate::merkle_tree::MerkleTreeWithHistory;
+use crate::U256;
+
+/// Authentication path of one tracked leaf.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IncrementalWitness {
+    pub position: usize,
+    pub leaf: U256,
+    path: Vec<U256>,
+}
+
+impl IncrementalWitness {
+    /// Siblings from the leaf level upwards (`pathElements` in the circuit).
+    pub fn path_elements(&self) -> &[U256] {
+        &self.path
+    }
+
+    /// Whether the tracked node is the right child at each level
+    /// (`pathIndices` in the circuit).
+    pub fn path_indices(&self) -> Vec<bool> {
+        (0..self.path.len()).map(|i| (self.position >> i) & 1 == 1).collect()
+    }
+}
+
+/// Frontier of a tree plus the witnesses of the leaves being tracked.
+pub struct WitnessSet {
+    tree: MerkleTreeWithHistory,
+    witnesses: Vec<IncrementalWitness>,
+}
+
+impl WitnessSet {
+    /// Starts from a tree's `filledSubtrees` and `nextIndex`, e.g. read from
+    /// the on-chain state right before the wallet's own deposit.
+    ///
+    /// `None` if the tree is full: nothing can be appended any more, and the
+    /// last leaf of a full tree is not part of its frontier.
+    pub fn from_frontier(levels: u32, filled_subtrees: &[U256], next_index: usize) -> Option<Self> {
+        assert_eq!(filled_subtrees.len(), levels as usize, "frontier should have one node per level");
+        if next_index >= 2_usize.saturating_pow(levels) {
+            return None;
+        }
+        let mut tree = MerkleTreeWithHistory::with_history(levels, 1);
+        for (node, filled) in tree.filledSubtrees.iter_mut().zip(filled_subtrees) {
+            **node = *filled;
+        }
+        tree.nextIndex = next_index;
+
+        // the root with the next leaf still empty: left siblings of its path
+        // come from the frontier, right ones are zero
+        let mut node = *tree.zeros[0];
+        for i in 0..(levels as usize) {
+            node = if (next_index >> i) & 1 == 1 {
+                *tree.hashLeftRight(&tree.filledSubtrees[i], &node)
+            } else {
+                *tree.hashLeftRight(&node, &tree.zeros[i])
+            };
+        }
+        *tree.roots[0] = node;
+        tree.root_leaf_counts[0] = next_index;
+        Some(Self { tree, witnesses: Vec::new() })
+    }
+
+    /// Appends a leaf that is not ours. Returns its index.
+    pub fn append(&mut self, leaf: &U256) -> Option<usize> {
+        let position = self.tree.nextIndex;
+        let witnesses = &mut self.witnesses;
+        self.tree.insert_visit(leaf, |level, node| {
+            for w in witnesses.iter_mut() {
+                if position >> level == (w.position >> level) ^ 1 {
+                    w.path[level] = *node;
+                }
+            }
+        })
+    }
+
+    /// Appends one of our leaves and starts tracking its path.
+    pub fn append_tracked(&mut self, leaf: &U256) -> Option<usize> {
+        let position = self.append(leaf)?;
+        let path = (0..(self.tree.levels as usize))
+            .map(|i| {
+                if (position >> i) & 1 == 1 {
+                    *self.tree.filledSubtrees[i]
+                } else {
+                    *self.tree.zeros[i]
+                }
+            })
+            .collect();
+        self.witnesses.push(IncrementalWitness { position, leaf: *leaf, path });
+        Some(position)
+    }
+
+    /// Stops tracking the leaf at `position`, e.g. once the note is spent.
+    pub fn forget(&mut self, position: usize) {
+        self.witnesses.retain(|w| w.position != position);
+    }
+
+    pub fn witness(&self, position: usize) -> Option<&IncrementalWitness> {
+        self.witnesses.iter().find(|w| w.position == position)
+    }
+
+    pub fn witnesses(&self) -> &[IncrementalWitness] {
+        &self.witnesses
+    }
+
+    pub fn next_index(&self) -> usize {
+        self.tree.nextIndex
+    }
+
+    /// Root of the tree after the last appended leaf.
+    pub fn root(&self) -> U256 {
+        *self.tree.getLastRoot()
+    }
+
+    /// Recomputes the root from a witness' leaf and path.
+    pub fn path_root(&self, witness: &IncrementalWitness) -> U256 {
+        let mut node = witness.leaf;
+        for (sibling, is_right) in witness.path.iter().zip(witness.path_indices()) {
+            node = if is_right {
+                *self.tree.hashLeftRight(sibling, &node)
+            } else {
+                *self.tree.hashLeftRight(&node, sibling)
+            };
+        }
+        node
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_witness_follows_tree() {
+        let mut mt = MerkleTreeWithHistory::new(3);
+        mt.insert(&U256::new(1));
+        mt.insert(&U256::new(2));
+        mt.insert(&U256::new(3));
+
+        let frontier: Vec<U256> = mt.filledSubtrees.iter().map(|n| **n).collect();
+        let mut ws = WitnessSet::from_frontier(3, &frontier, mt.nextIndex).unwrap();
+        assert_eq!(ws.root(), *mt.getLastRoot());
+
+        assert_eq!(ws.append_tracked(&U256::new(4)), Some(3));
+        mt.insert(&U256::new(4));
+        assert_eq!(ws.root(), *mt.getLastRoot());
+        assert_eq!(ws.path_root(ws.witness(3).unwrap()), *mt.getLastRoot());
+
+        for i in 5..8 {
+            ws.append(&U256::new(i));
+            mt.insert(&U256::new(i));
+        }
+        assert_eq!(ws.append_tracked(&U256::new(8)), Some(7));
+        mt.insert(&U256::new(8));
+
+        assert_eq!(ws.root(), *mt.getLastRoot());
+        for w in ws.witnesses() {
+            assert_eq!(ws.path_root(w), *mt.getLastRoot());
+        }
+        assert_eq!(ws.witness(7).unwrap().path_indices(), vec![true, true, true]);
+        assert!(ws.append(&U256::new(9)).is_none());
+
+        ws.forget(3);
+        assert!(ws.witness(3).is_none());
+        assert_eq!(ws.witnesses().len(), 1);
+    }
+
+    #[test]
+    fn test_frontier_root() {
+        let mut mt = MerkleTreeWithHistory::new(2);
+        for i in 0..4 {
+            let frontier: Vec<U256> = mt.filledSubtrees.iter().map(|n| **n).collect();
+            let ws = WitnessSet::from_frontier(2, &frontier, mt.nextIndex).unwrap();
+            assert_eq!(ws.root(), *mt.getLastRoot(), "{}", i);
+            mt.insert(&U256::new(i + 1));
+        }
+
+        let frontier: Vec<U256> = mt.filledSubtrees.iter().map(|n| **n).collect();
+        assert!(WitnessSet::from_frontier(2, &frontier, 4).is_none());
+        assert!(WitnessSet::from_frontier(2, &frontier, 5).is_none());
+    }
+}